@@ -0,0 +1,95 @@
+use crate::usage::UsageContext;
+use std::path::Path;
+use tokio::process::Command;
+
+async fn record_usage(usage: &UsageContext<'_>, model: &str, stdout: &str) {
+    usage
+        .tracker
+        .record_aider(usage.role, usage.task, model, stdout)
+        .await;
+}
+
+/// Runs aider with CONTEXT.md loaded, returning its stdout when it ran successfully.
+pub async fn run(
+    dir: &Path,
+    model: &str,
+    message: &str,
+    files: &[String],
+    usage: &UsageContext<'_>,
+) -> Option<String> {
+    let output = Command::new("aider")
+        .current_dir(dir)
+        .arg("--model")
+        .arg(model)
+        .arg("--message")
+        .arg(message)
+        .arg("--load")
+        .arg("CONTEXT.md")
+        .arg("--yes-always")
+        .arg("--no-suggest-shell-commands")
+        .args(files)
+        .output()
+        .await;
+
+    match output {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            record_usage(usage, model, &stdout).await;
+            Some(stdout)
+        }
+        Ok(output) => {
+            eprintln!(
+                "Aider command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            None
+        }
+        Err(e) => {
+            eprintln!("Failed to run aider: {}", e);
+            None
+        }
+    }
+}
+
+/// Asks aider a question without letting it edit anything. `read_files` are added read-only.
+pub async fn ask(
+    dir: &Path,
+    model: &str,
+    message: &str,
+    read_files: &[String],
+    usage: &UsageContext<'_>,
+) -> Option<String> {
+    let mut cmd = Command::new("aider");
+    cmd.current_dir(dir)
+        .arg("--model")
+        .arg(model)
+        .arg("--chat-mode")
+        .arg("ask")
+        .arg("--message")
+        .arg(message)
+        .arg("--yes-always")
+        .arg("--no-suggest-shell-commands")
+        .arg("--no-auto-commits");
+    for file in read_files {
+        cmd.arg("--read").arg(file);
+    }
+
+    match cmd.output().await {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            record_usage(usage, model, &stdout).await;
+            Some(stdout)
+        }
+        Ok(output) => {
+            eprintln!(
+                "Aider command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            None
+        }
+        Err(e) => {
+            eprintln!("Failed to run aider: {}", e);
+            None
+        }
+    }
+}
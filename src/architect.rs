@@ -1,4 +1,5 @@
-use crate::{ActivityMode, AppStateWithDir};
+use crate::events::Verbosity;
+use crate::{code_model_name, ActivityMode, AppStateWithDir};
 use std::fs;
 use std::process::Command;
 use std::sync::Arc;
@@ -30,6 +31,11 @@ pub async fn architect_loop(
         };
 
         if !should_continue {
+            state_with_dir.set_role_state("architect", "idle").await;
+            continue;
+        }
+        if state_with_dir.usage.is_paused("architect").await {
+            state_with_dir.set_role_state("architect", "paused, budget exceeded").await;
             continue;
         }
 
@@ -47,9 +53,7 @@ pub async fn architect_loop(
             if let (Ok(project_modified), Ok(architecture_modified)) =
                 (project_meta.modified(), architecture_meta.modified())
             {
-                let project_newer = project_modified > architecture_modified;
-
-                project_newer
+                project_modified > architecture_modified
             } else {
                 false
             }
@@ -58,6 +62,8 @@ pub async fn architect_loop(
         };
 
         if should_run_aider {
+            let _repo = state_with_dir.repo_lock.write("architect").await;
+            state_with_dir.set_role_state("architect", "updating ARCHITECTURE.md").await;
             println!("🏗️ Updating ARCHITECTURE.md from project requirements...");
             let mut cmd = Command::new("aider");
             cmd.current_dir(&project_dir)
@@ -83,10 +89,21 @@ pub async fn architect_loop(
                         String::from_utf8_lossy(&output.stderr)
                     );
                 } else {
+                    state_with_dir
+                        .usage
+                        .record_aider(
+                            "architect",
+                            None,
+                            code_model_name(&state_with_dir),
+                            &String::from_utf8_lossy(&output.stdout),
+                        )
+                        .await;
                     println!("✨ Aider finished updating ARCHITECTURE.md");
+                    state_with_dir.comment(Verbosity::Verbose, "ARCHITECTURE.md was updated from the project requirements");
                 }
             }
         }
+        state_with_dir.set_role_state("architect", "up to date").await;
     }
     println!("Architect thread shutting down cleanly");
 }
@@ -0,0 +1,54 @@
+use crate::providers::Provider;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: &str) -> Self {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+}
+
+/// Sends `messages` to the provider's OpenAI-compatible `chat/completions` endpoint,
+/// returning the reply and the response's `usage` object.
+pub async fn complete(provider: &Provider, messages: &[ChatMessage]) -> Result<(String, serde_json::Value), String> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = provider
+        .request(&client, Method::POST, "chat/completions")
+        .json(&serde_json::json!({
+            "model": provider.model,
+            "messages": messages,
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", provider.base_url, e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Chat request failed with {}: {}", status, body));
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse chat response: {}", e))?;
+    let reply = body["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or_else(|| "Chat response had no message content".to_string())?
+        .to_string();
+    Ok((reply, body["usage"].clone()))
+}
@@ -1,3 +1,4 @@
+use crate::voice::{check_language, check_voice};
 use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
@@ -13,6 +14,16 @@ pub enum Commands {
     Serve(ServeArgs),
     /// Initialize a new project directory
     Init(InitArgs),
+    /// Check that the environment is ready to serve a project
+    Doctor(DoctorArgs),
+    /// Plan and build a project from a requirements file, without the web UI
+    Run(RunArgs),
+    /// Show the mode, role states, current task and recent results of a running server
+    Status(ClientArgs),
+    /// List the tasks in TASKS.md with their status
+    Tasks(ClientArgs),
+    /// Switch a running server between planning and developing
+    Mode(ModeArgs),
 }
 
 #[derive(Parser)]
@@ -20,6 +31,132 @@ pub struct InitArgs {
     /// Directory path to initialize
     #[arg(default_value = ".")]
     pub dir: String,
+
+    /// Project language, skips the interactive prompt
+    #[arg(short, long, conflicts_with = "template")]
+    pub language: Option<String>,
+
+    /// Overwrite existing CONTEXT.md, Makefile and template files
+    #[arg(short, long, conflicts_with = "merge")]
+    pub force: bool,
+
+    /// Merge into existing CONTEXT.md and Makefile instead of keeping them as they are
+    #[arg(short, long)]
+    pub merge: bool,
+
+    /// Do not initialize a git repository
+    #[arg(long)]
+    pub no_git: bool,
+
+    /// Template to apply: a language name or a directory of files to copy
+    #[arg(short, long)]
+    pub template: Option<String>,
+
+    /// Project name written to PROJECT.md, defaults to the directory name
+    #[arg(short, long)]
+    pub name: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct DoctorArgs {
+    /// Project directory to check
+    #[arg(short = 'd', long, default_value = "./")]
+    pub project_dir: String,
+
+    /// Port the server will run on
+    #[arg(short, long, default_value = "49999")]
+    pub port: u16,
+
+    /// Realtime model the voice interface will use [default: gpt-4o-realtime-preview-2024-12-17]
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    /// Model aider will use for code changes
+    #[arg(short = 'c', long = "code-model")]
+    pub code_model: Option<String>,
+
+    /// Providers file with base URLs, keys and headers [default: .colossus/providers.json]
+    #[arg(long)]
+    pub providers: Option<String>,
+
+    /// Only check that API keys are set, without calling the APIs
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Set by `run`, which opens no port and starts no voice session
+    #[arg(skip)]
+    pub headless: bool,
+}
+
+#[derive(Parser)]
+pub struct ClientArgs {
+    /// URL of the running Colossus server
+    #[arg(short, long, default_value = "http://localhost:49999")]
+    pub server: String,
+
+    /// Project directory to read when the server isn't running
+    #[arg(short = 'd', long, default_value = "./")]
+    pub project_dir: String,
+}
+
+#[derive(Parser)]
+pub struct ModeArgs {
+    /// Mode to switch to
+    #[arg(value_parser = ["planning", "developing"])]
+    pub mode: String,
+
+    #[command(flatten)]
+    pub client: ClientArgs,
+}
+
+#[derive(Parser)]
+pub struct RunArgs {
+    /// Requirements text file, used in place of the interview transcript
+    pub requirements: String,
+
+    /// Project directory to build in
+    #[arg(short = 'd', long, default_value = "./")]
+    pub project_dir: String,
+
+    /// Model aider uses for every role
+    #[arg(short = 'c', long = "code-model")]
+    pub code_model: String,
+
+    /// Stronger model used for the last attempts at fixing build or test failures
+    #[arg(long)]
+    pub escalation_model: Option<String>,
+
+    /// Skip the code review step before tasks are marked done
+    #[arg(long)]
+    pub no_review: bool,
+
+    /// Test-first mode: write failing tests for each task before implementing it
+    #[arg(long)]
+    pub tdd: bool,
+
+    /// Number of parallel developer workers, each in its own git worktree
+    #[arg(long, default_value = "1")]
+    pub workers: usize,
+
+    /// Session budget in USD, the run stops once total spending reaches it
+    #[arg(long)]
+    pub budget: Option<f64>,
+
+    /// Budget in USD for a single role, e.g. `developer=5` (can be repeated)
+    #[arg(long = "role-budget", value_parser = parse_role_budget)]
+    pub role_budgets: Vec<(String, f64)>,
+
+    /// Providers file with base URLs, keys and headers [default: .colossus/providers.json]
+    #[arg(long)]
+    pub providers: Option<String>,
+
+    /// Minutes to wait for the planning documents to settle
+    #[arg(long, default_value = "30")]
+    pub planning_timeout: u64,
+
+    /// Minutes the whole run may take before giving up
+    #[arg(long, default_value = "240")]
+    pub timeout: u64,
 }
 
 #[derive(Parser)]
@@ -32,55 +169,29 @@ pub struct ServeArgs {
     #[arg(short, long, default_value = "49999")]
     pub port: u16,
 
-    /// OpenAI model name to use
-    #[arg(short, long, default_value = "gpt-4o-realtime-preview-2024-12-17")]
-    pub model: String,
+    /// OpenAI realtime model name to use [default: gpt-4o-realtime-preview-2024-12-17]
+    #[arg(short, long)]
+    pub model: Option<String>,
 
     // Preferred language
-    #[arg(short = 'l', long, default_value = "english")]
+    #[arg(short = 'l', long, default_value = "english", value_parser = check_language)]
     pub preferred_language: String,
 
-    // instructions
-    #[arg(
-        short,
-        long,
-        default_value = "
-        <name>Product Manager Interviewer</name>
-        <voice_quality>You speak with a professional but friendly tone, asking thoughtful questions</voice_quality>
-        <personality>
-        * You are a senior product manager conducting an interview about a new application
-        * Your goal is to deeply understand the user's needs and vision
-        * You ask clarifying questions to get specific details
-        * You help refine ideas by suggesting alternatives
-        * You focus on user needs, business value, and technical feasibility
-        </personality>
-        <interview_approach>
-        * Start by asking about the core purpose of the application
-        * Explore the target users and their needs
-        * Discuss key features and functionality
-        * Probe for technical requirements and constraints
-        * Suggest potential improvements or alternatives
-        * Help prioritize features based on value and effort
-        </interview_approach>
-        <responses>
-        * Keep responses conversational and professional
-        * Ask one question at a time
-        * Paraphrase to confirm understanding
-        * Suggest ideas but don't dominate the conversation
-        * Avoid technical jargon unless the user introduces it
-        </responses>
-        <purpose>
-        I am here to help you clarify and refine your application idea through a structured interview process.
-        </purpose>"
-    )]
-    pub instructions: String,
+    /// Custom instructions replacing the product interviewer persona
+    #[arg(short, long)]
+    pub instructions: Option<String>,
+
+    /// Persona voice sessions take on instead of following the mode, e.g. architect or qa
+    #[arg(long)]
+    pub persona: Option<String>,
 
     // voice
     #[arg(
         short,
         long,
         default_value = "ash",
-        help = "Supported voices are alloy, ash, coral, echo, fable, onyx, nova, sage and shimmer."
+        value_parser = check_voice,
+        help = "Supported voices are alloy, ash, ballad, coral, echo, sage, shimmer and verse."
     )]
     pub voice: String,
 
@@ -91,4 +202,54 @@ pub struct ServeArgs {
         help = "OpenAI model to use for code analysis"
     )]
     pub code_model: Option<String>,
+
+    /// Stronger model used for the last attempts at fixing build or test failures
+    #[arg(long)]
+    pub escalation_model: Option<String>,
+
+    /// Base URL of the OpenAI-compatible chat API behind text chat, e.g. a local model server
+    #[arg(long)]
+    pub chat_base_url: Option<String>,
+
+    /// Model used for text chat [default: gpt-4o-mini]
+    #[arg(long)]
+    pub chat_model: Option<String>,
+
+    /// Providers file with base URLs, keys and headers [default: .colossus/providers.json]
+    #[arg(long)]
+    pub providers: Option<String>,
+
+    /// How much development progress the voice assistant comments on
+    #[arg(long, default_value = "normal", value_parser = ["off", "important", "normal", "verbose"])]
+    pub commentary: String,
+
+    /// Skip the code review step before tasks are marked done
+    #[arg(long)]
+    pub no_review: bool,
+
+    /// Test-first mode: write failing tests for each task before implementing it
+    #[arg(long)]
+    pub tdd: bool,
+
+    /// Number of parallel developer workers, each in its own git worktree
+    #[arg(long, default_value = "1")]
+    pub workers: usize,
+
+    /// Session budget in USD, every role pauses once total spending reaches it
+    #[arg(long)]
+    pub budget: Option<f64>,
+
+    /// Budget in USD for a single role, e.g. `developer=5` (can be repeated)
+    #[arg(long = "role-budget", value_parser = parse_role_budget)]
+    pub role_budgets: Vec<(String, f64)>,
+}
+
+fn parse_role_budget(value: &str) -> Result<(String, f64), String> {
+    let (role, budget) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Expected ROLE=USD, got '{}'", value))?;
+    let budget = budget
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid budget '{}' for role {}", budget, role))?;
+    Ok((role.to_string(), budget))
 }
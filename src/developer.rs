@@ -1,85 +1,349 @@
 use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+use tokio::process::Command;
+use crate::{AppStateWithDir, ActivityMode};
+use crate::{aider, diagnostics, git, progress, run_log};
+use crate::events::Verbosity;
+use crate::progress::{Progress, ProgressTracker};
+use crate::usage::UsageContext;
+use crate::reviewer::review_until_approved;
+use crate::tasks;
+use std::path::Path;
 
-async fn handle_make_test(project_dir: &str, model: &str) -> bool {
-    println!("Running make test...");
-    let test_output = Command::new("make")
-        .current_dir(project_dir)
-        .arg("test")
-        .output()
-        .await
-        .expect("Failed to execute make test");
+const FIX_ATTEMPTS: usize = 5;
+// Times the loop may pick the same task before deciding it is stuck
+const MAX_TASK_ATTEMPTS: usize = 3;
+// Cap on files pulled into aider's context from error locations
+const MAX_REFERENCED_FILES: usize = 10;
 
-    if !test_output.status.success() {
-        let stderr = String::from_utf8_lossy(&test_output.stderr);
-        let stdout = String::from_utf8_lossy(&test_output.stdout);
+// Files named in the errors that actually exist inside the project
+fn referenced_files(project_dir: &str, output: &str) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for diagnostic in diagnostics::parse_diagnostics(output) {
+        let Some(file) = diagnostic.file else {
+            continue;
+        };
+        let path = Path::new(&file);
+        if path.is_absolute() || file.contains("..") || !Path::new(project_dir).join(path).is_file() {
+            continue;
+        }
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files.truncate(MAX_REFERENCED_FILES);
+    files
+}
 
-        // Send test error to aider to fix
-        println!("🔧 Attempting to fix test failures with aider...");
-        // give me the complete command
-        let fix_message = format!("Fix these test failures:\nSTDOUT:\n{}\nSTDERR:\n{}", stdout, stderr);
-        let _output = Command::new("aider")
-            .current_dir(project_dir)
-            .arg("--model")
-            .arg(model)
-            .arg("--message")
-            .arg(&fix_message)
-            .arg("--load")
-            .arg("CONTEXT.md")
-            .arg("--yes-always")
-            .arg("--no-suggest-shell-commands")
+struct FixAttempt {
+    errors: String,
+    changed_files: bool,
+}
+
+/// State carried across the attempts at getting one make target to pass.
+struct FixLoop<'a> {
+    project_dir: &'a str,
+    model: &'a str,
+    escalation_model: Option<&'a str>,
+    escalate: bool,
+    history: Vec<FixAttempt>,
+    usage: UsageContext<'a>,
+}
+
+impl FixLoop<'_> {
+    /// Asks aider to fix a failing build or test run. Every attempt sends the summarized
+    /// errors plus those of earlier attempts, and escalates: from attempt 2 the files named
+    /// in the errors are added to the context, from attempt 3 aider plans the fix before
+    /// editing, and from attempt 4 (or earlier if `escalate` is set) the escalation model
+    /// is used if one is configured.
+    async fn fix_failure(&mut self, what: &str, output: &str, attempt: usize) {
+        let dir = Path::new(self.project_dir);
+        let errors = diagnostics::summarize(output);
+
+        let mut message = format!(
+            "Fix these {} (attempt {} of {}):\n{}",
+            what, attempt, FIX_ATTEMPTS, errors
+        );
+        if !self.history.is_empty() {
+            message.push_str("\n\nPrevious attempts did not fix the problem. Errors seen on earlier attempts:");
+            for (i, previous) in self.history.iter().enumerate() {
+                message.push_str(&format!("\nAttempt {}:\n{}", i + 1, previous.errors));
+                if !previous.changed_files {
+                    message.push_str("\n(this attempt made no file changes)");
+                }
+            }
+            if self.history.last().map(|h| &h.errors) == Some(&errors) {
+                message.push_str("\n\nThe last attempt changed nothing about these errors, so try a different approach.");
+            }
+        }
+
+        let files = if attempt >= 2 {
+            referenced_files(self.project_dir, output)
+        } else {
+            Vec::new()
+        };
+
+        if attempt >= 3 {
+            println!("🧠 Asking aider to plan the fix first...");
+            let plan_request = format!(
+                "Before changing any code, explain the root cause of these {} and give a short step by step plan to fix them:\n{}",
+                what, errors
+            );
+            if let Some(plan) = aider::ask(dir, self.model, &plan_request, &files, &self.usage).await {
+                message.push_str(&format!("\n\nFollow this plan:\n{}", plan.trim()));
+            }
+        }
+
+        let fix_model = match self.escalation_model {
+            Some(stronger) if attempt >= 4 || self.escalate => {
+                println!("⬆️  Escalating to {}", stronger);
+                stronger
+            }
+            _ => self.model,
+        };
+
+        println!("🔧 Attempting to fix {} with aider...", what);
+        aider::run(dir, fix_model, &message, &files, &self.usage).await;
+        println!("✨ Aider finished attempting {} fix", what);
+
+        self.history.push(FixAttempt {
+            errors,
+            changed_files: true,
+        });
+    }
+
+    /// Runs make `target` and asks aider to fix it when it fails. Returns whether it
+    /// passed, or an error when make could not be run at all.
+    async fn handle_make(&mut self, target: &str, attempt: usize) -> Result<bool, String> {
+        println!("Running make {}...", target);
+        let make_output = Command::new("make")
+            .current_dir(self.project_dir)
+            .arg(target)
             .output()
             .await
-            .expect("Failed to execute aider command");
-        
-        println!("✨ Aider finished attempting test fix");
-        false
-    } else {
-        println!("Make test succeeded");
-        true
+            .map_err(|e| format!("Failed to execute make {} in {}: {}", target, self.project_dir, e))?;
+
+        let task = self.usage.task.unwrap_or("no task");
+        if make_output.status.success() {
+            println!("Make {} succeeded", target);
+            run_log::record(
+                Path::new(self.project_dir),
+                &format!("make {} passed for {} on attempt {}", target, task, attempt),
+            );
+            return Ok(true);
+        }
+
+        let output = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&make_output.stdout),
+            String::from_utf8_lossy(&make_output.stderr)
+        );
+        let diagnostics = diagnostics::parse_diagnostics(&output);
+        run_log::record(
+            Path::new(self.project_dir),
+            &format!(
+                "make {} failed for {} on attempt {}: {}",
+                target,
+                task,
+                attempt,
+                diagnostics
+                    .first()
+                    .map(|d| format!("{} ({} errors)", d, diagnostics.len()))
+                    .unwrap_or_else(|| "no recognised errors".to_string())
+            ),
+        );
+        let what = if target == "build" { "build errors" } else { "test failures" };
+        self.fix_failure(what, &output, attempt).await;
+        Ok(false)
     }
 }
-use tokio::sync::Mutex;
-use tokio::time::{self, Duration};
-use tokio::process::Command;
-use crate::{AppStateWithDir, ActivityMode};
 
-async fn handle_make_build(project_dir: &str, model: &str) -> bool {
-    // Run make build
-    println!("Running make build...");
-    let build_output = Command::new("make")
-        .current_dir(project_dir)
-        .arg("build")
-        .output()
+/// Runs make build and make test, letting aider fix failures for up to five attempts
+/// each. Stops early when fixes go in circles: the same errors keep coming back, or an
+/// attempt undoes an earlier one. Returns why when either never passes; the caller
+/// decides whether that needs a human.
+pub async fn build_and_test(
+    project_dir: &str,
+    task: &str,
+    model: &str,
+    state_with_dir: &AppStateWithDir,
+) -> Result<(), String> {
+    let dir = Path::new(project_dir);
+
+    for (target, label) in [("build", "Build"), ("test", "Test")] {
+        let mut fix_loop = FixLoop {
+            project_dir,
+            model,
+            escalation_model: state_with_dir.escalation_model.as_deref(),
+            escalate: false,
+            history: Vec::new(),
+            usage: state_with_dir.usage.context("developer", Some(task)),
+        };
+        let mut tracker = ProgressTracker::new(dir).await;
+        let mut success = false;
+
+        for attempt in 1..=FIX_ATTEMPTS {
+            println!("{} attempt {} of {}", label, attempt, FIX_ATTEMPTS);
+            match fix_loop.handle_make(target, attempt).await {
+                Ok(true) => {
+                    success = true;
+                    break;
+                }
+                Ok(false) => {}
+                Err(e) => return Err(e),
+            }
+
+            // Only worth mentioning once it looks like more than a quick fix
+            if attempt == 2 {
+                state_with_dir.comment(
+                    Verbosity::Normal,
+                    &format!("{} keeps failing for {}, still trying to fix it", label, task),
+                );
+            }
+
+            match tracker.record(dir).await {
+                Progress::Reverted => {
+                    return Err(format!(
+                        "{} fixes are oscillating: attempt {} undid an earlier attempt's edits",
+                        label, attempt
+                    ));
+                }
+                Progress::Unchanged => {
+                    if let Some(last) = fix_loop.history.last_mut() {
+                        last.changed_files = false;
+                    }
+                }
+                Progress::Changed => {}
+            }
+
+            let errors: Vec<&str> = fix_loop.history.iter().map(|h| h.errors.as_str()).collect();
+            if progress::repeated_failure(&errors) {
+                if fix_loop.escalation_model.is_some() && !fix_loop.escalate {
+                    fix_loop.escalate = true;
+                } else {
+                    return Err(format!(
+                        "{} failed with the same errors {} times in a row:\n{}",
+                        label,
+                        progress::REPEAT_LIMIT,
+                        errors[errors.len() - 1]
+                    ));
+                }
+            }
+        }
+        if !success {
+            return Err(format!("{} failed after {} attempts", label, FIX_ATTEMPTS));
+        }
+    }
+    Ok(())
+}
+
+/// For a task aider made no changes for even after being re-prompted: aider believes the
+/// task is already done, which is accepted only if build and tests in `work_dir` agree.
+/// Then the task is marked done in the project's TASKS.md, taking the repository lock as
+/// `writer` unless the caller already holds it.
+pub async fn accept_unchanged_task(
+    work_dir: &str,
+    task: &tasks::Task,
+    label: &str,
+    model: &str,
+    state_with_dir: &AppStateWithDir,
+    writer: Option<&str>,
+) -> Result<(), String> {
+    build_and_test(work_dir, label, model, state_with_dir)
         .await
-        .expect("Failed to execute make build");
+        .map_err(|e| format!("Aider made no changes for {} and {}", label, e))?;
+
+    let _repo = match writer {
+        Some(who) => Some(state_with_dir.repo_lock.write(who).await),
+        None => None,
+    };
+    let project_path = Path::new(&state_with_dir.project_dir);
+    tasks::mark_task_done(&state_with_dir.project_dir, task.number)?;
+    git::commit_paths(project_path, &["TASKS.md"], &format!("Mark task {} done", task.label)).await?;
+    run_log::record(
+        project_path,
+        &format!("Skipped {}: aider made no changes and build and tests pass", label),
+    );
+    Ok(())
+}
+
+/// Re-prompts with explicit context if aider changed nothing since `base`. Returns false
+/// if there are still no changes afterwards.
+pub async fn ensure_progress(
+    project_dir: &str,
+    base: &str,
+    task: &str,
+    model: &str,
+    usage: &UsageContext<'_>,
+) -> bool {
+    let dir = Path::new(project_dir);
+    if git::has_changes_since(dir, base).await {
+        return true;
+    }
+
+    println!("⚠️  Aider made no changes for {}, re-prompting...", task);
+    let message = format!(
+        "You made no file changes for {}. Read the task in TASKS.md again together with ARCHITECTURE.md and the existing code, \
+then implement whatever is missing and add tests for it. Do not edit TASKS.md.",
+        task
+    );
+    aider::run(dir, model, &message, &[], usage).await;
+    git::has_changes_since(dir, base).await
+}
+
+const TDD_ATTEMPTS: usize = 3;
+
+/// Test-first phase: asks aider for tests only and checks that make test fails before
+/// anything is implemented. Returns the working directory with the tests in place, as a
+/// tree to measure the implementation's progress from, or why the phase failed: the
+/// tests kept passing, or make could not be run.
+pub async fn write_failing_tests(
+    project_dir: &str,
+    task: &str,
+    model: &str,
+    usage: &UsageContext<'_>,
+) -> Result<String, String> {
+    let dir = Path::new(project_dir);
+    let mut message = format!(
+        "Write tests for {} ONLY, following TEST_STRATEGY.md. Do not implement the task yet; the tests must fail until it is implemented.",
+        task
+    );
 
-    if !build_output.status.success() {
-        let stderr = String::from_utf8_lossy(&build_output.stderr);
-        let stdout = String::from_utf8_lossy(&build_output.stdout);
+    for attempt in 1..=TDD_ATTEMPTS {
+        println!("🧪 Writing failing tests, attempt {} of {}", attempt, TDD_ATTEMPTS);
+        aider::run(dir, model, &message, &[], usage).await;
 
-        // Send build error to aider to fix
-        println!("🔧 Attempting to fix build error with aider...");
-        let fix_message = format!("Fix this build error:\nSTDOUT:\n{}\nSTDERR:\n{}", stdout, stderr);
-        let _output = Command::new("aider")
+        println!("Running make test...");
+        let test_output = Command::new("make")
             .current_dir(project_dir)
-            .arg("--model")
-            .arg(model)
-            .arg("--message")
-            .arg(&fix_message)
-            .arg("--load")
-            .arg("CONTEXT.md")
-            .arg("--yes-always")
-            .arg("--no-suggest-shell-commands")
+            .arg("test")
             .output()
             .await
-            .expect("Failed to execute aider command");
-        
-        println!("✨ Aider finished attempting build fix");
-        false
-    } else {
-        println!("Make build succeeded");
-        true
+            .map_err(|e| format!("Failed to execute make test in {}: {}", project_dir, e))?;
+
+        if !test_output.status.success() {
+            run_log::record(dir, &format!("TDD red: tests for {} fail before implementation", task));
+            return match git::snapshot_tree(dir).await {
+                Some(tree) => Ok(tree),
+                None => Ok(git::head_commit(dir).await),
+            };
+        }
+
+        message = format!(
+            "The tests for {} pass even though it is not implemented yet. Change them so they check the behaviour the task adds and fail until it is implemented. Do not implement the task.",
+            task
+        );
     }
+
+    run_log::record(
+        dir,
+        &format!("TDD red: tests for {} still passed before implementation after {} attempts", task, TDD_ATTEMPTS),
+    );
+    Err(format!(
+        "The tests written for {} still passed before implementation after {} attempts, so they don't test it",
+        task, TDD_ATTEMPTS
+    ))
 }
 
 pub async fn developer_loop(
@@ -88,6 +352,8 @@ pub async fn developer_loop(
     state_with_dir: Arc<AppStateWithDir>,
 ) {
     let mut interval = time::interval(Duration::from_secs(30));
+    let mut last_task_label = String::new();
+    let mut task_attempts = 0;
 
     loop {
         interval.tick().await;
@@ -115,88 +381,128 @@ pub async fn developer_loop(
             
             if is_error {
                 println!("⚠️  Development halted - human intervention required to fix critical errors!");
+                state_with_dir.set_role_state("developer", "halted, needs a human").await;
+            } else {
+                state_with_dir.set_role_state("developer", "idle").await;
             }
+            task_attempts = 0;
             continue;
         }
 
+        if state_with_dir.usage.is_paused("developer").await {
+            state_with_dir.set_role_state("developer", "paused, budget exceeded").await;
+            continue;
+        }
+
+        // Keep everyone else off the tree until this task's attempt is over
+        let _repo = state_with_dir.repo_lock.write("developer").await;
+
         // Run aider command
         // Get the code model from state
         let code_model = state_with_dir.code_model.clone();
 
         println!("Running aider in directory: {}", project_dir);
         let model = code_model.as_ref().expect("Code model should be set from CLI params");
-        let _output = Command::new("aider")
-            .current_dir(&project_dir)
-            .arg("--model")
-            .arg(model)
-            .arg("--message")
-            
-            .arg("find the first UNCOMPLETED task (one without a checkmark ✓) in TASKS.md, working in strict numerical order from top to bottom, implement it, and create some way to test it")
-            .arg("--load")
-            .arg("CONTEXT.md")
-            .arg("--yes-always")
-            .arg("--no-suggest-shell-commands")
-            .output()
-            .await
-            .expect("Failed to execute aider command");
-    
-        println!("✨ Aider finished task assignment");
+        let base = git::head_commit(Path::new(&project_dir)).await;
+        let current_task = tasks::read_tasks(&project_dir).into_iter().find(|t| !t.done);
+        let task_label = current_task
+            .as_ref()
+            .map(|t| format!("task {}: {}", t.label, t.title))
+            .unwrap_or_else(|| "the first uncompleted task in TASKS.md".to_string());
 
-        // Run make build after aider with retries
-        let mut build_success = false;
-        for attempt in 1..=5 {
-            println!("Build attempt {} of 5", attempt);
-            if handle_make_build(&project_dir, model).await {
-                build_success = true;
-                break;
-            }
-            if attempt == 5 {
-                println!("SOMETHING IS SERIOUSLY WRONG - Build failed after 5 attempts");
-                let mut mode = state_with_dir.activity_mode.lock().await;
-                *mode = ActivityMode::ErrorNeedsHuman;
+        state_with_dir
+            .set_role_state("developer", &format!("working on {}", task_label))
+            .await;
+        if task_label == last_task_label {
+            task_attempts += 1;
+        } else {
+            last_task_label = task_label.clone();
+            task_attempts = 1;
+            if let Some(task) = &current_task {
+                state_with_dir.comment(Verbosity::Normal, &format!("Starting task {}, {}", task.label, task.title));
             }
         }
-        if !build_success {
-            continue; // Restart loop after all attempts failed
+        if task_attempts > MAX_TASK_ATTEMPTS {
+            state_with_dir
+                .needs_human(&format!(
+                    "{} was attempted {} times without being marked done",
+                    task_label, MAX_TASK_ATTEMPTS
+                ))
+                .await;
+            continue;
         }
 
-        // Run make test after successful build with retries
-        let mut test_success = false;
-        for attempt in 1..=5 {
-            println!("Test attempt {} of 5", attempt);
-            if handle_make_test(&project_dir, model).await {
-                test_success = true;
-                break;
+        let usage = state_with_dir.usage.context("developer", Some(&task_label));
+        let mut message = "find the first UNCOMPLETED task (one without a checkmark ✓) in TASKS.md, working in strict numerical order from top to bottom, implement it, and create some way to test it".to_string();
+        // Progress is measured after the tests, which alone don't implement anything
+        let mut progress_base = base.clone();
+        if state_with_dir.tdd {
+            match write_failing_tests(&project_dir, &task_label, model, &usage).await {
+                Ok(tests) => progress_base = tests,
+                Err(e) => {
+                    state_with_dir.needs_human(&e).await;
+                    continue;
+                }
             }
-            if attempt == 5 {
-                println!("SOMETHING IS SERIOUSLY WRONG - Tests failed after 5 attempts");
-                let mut mode = state_with_dir.activity_mode.lock().await;
-                *mode = ActivityMode::ErrorNeedsHuman;
+            message = format!(
+                "Now implement {} so the tests you just wrote pass. Do not weaken or delete those tests.",
+                task_label
+            );
+        }
+
+        aider::run(Path::new(&project_dir), model, &message, &[], &usage).await;
+        println!("✨ Aider finished task assignment");
+
+        if !ensure_progress(&project_dir, &progress_base, &task_label, model, &usage).await {
+            let Some(task) = &current_task else {
+                state_with_dir
+                    .needs_human(&format!("Aider made no changes for {} even after being re-prompted", task_label))
+                    .await;
+                continue;
+            };
+            // The repository is already held for this whole attempt
+            if let Err(e) = accept_unchanged_task(&project_dir, task, &task_label, model, &state_with_dir, None).await {
+                state_with_dir.needs_human(&e).await;
             }
+            continue;
         }
-        if !test_success {
+
+        if let Err(e) = build_and_test(&project_dir, &task_label, model, &state_with_dir).await {
+            state_with_dir.needs_human(&e).await;
             continue; // Restart loop after all attempts failed
         }
-            
-            // Tell aider to mark the completed task
-            println!("Marking off task complete!");
-            let output = Command::new("aider")
-                .current_dir(&project_dir)
-                .arg("--model")
-                .arg(model)
-                .arg("--message")
-                .arg("Mark the task we just completed in TASKS.md as done")
-                .arg("--load")
-                .arg("CONTEXT.md")
-                .arg("--yes-always")
-                .arg("--no-suggest-shell-commands")
-                .output()
-                .await
-                .expect("Failed to execute aider command");
-            
+        if state_with_dir.tdd {
+            run_log::record(Path::new(&project_dir), &format!("TDD green: tests for {} pass after implementation", task_label));
+        }
+
+        if state_with_dir.review {
+            if let Err(e) = review_until_approved(Path::new(&project_dir), &base, &task_label, model, &state_with_dir).await {
+                state_with_dir.needs_human(&e).await;
+                continue;
+            }
+        }
+
+        // Tell aider to mark the completed task
+        println!("Marking off task complete!");
+        if let Some(output) = aider::run(
+            Path::new(&project_dir),
+            model,
+            "Mark the task we just completed in TASKS.md as done",
+            &[],
+            &usage,
+        )
+        .await
+        {
             // Print aider's response
             println!("Aider response:");
-            println!("{}", String::from_utf8_lossy(&output.stdout));
+            println!("{}", output);
+        }
+        if let Some(task) = &current_task {
+            state_with_dir.comment(
+                Verbosity::Important,
+                &format!("Task {}, {}, is done and build and tests pass", task.label, task.title),
+            );
         }
+    }
     println!("Developer thread shutting down cleanly");
 }
@@ -0,0 +1,284 @@
+use std::fmt;
+
+// Cap on how many errors are sent to aider, the first ones are usually the root cause
+const MAX_DIAGNOSTICS: usize = 20;
+// Lines of raw output to fall back on when no known error format was recognised
+const FALLBACK_LINES: usize = 60;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: {}", file, line, self.message),
+            (Some(file), None) => write!(f, "{}: {}", file, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// Splits "path:line:col" or "path:line" into its file and line
+fn split_location(location: &str) -> Option<(String, u32)> {
+    let mut parts = location.trim().rsplitn(3, ':');
+    let last = parts.next()?;
+    let middle = parts.next()?;
+    match parts.next() {
+        Some(file) if middle.parse::<u32>().is_ok() => Some((file.to_string(), middle.parse().ok()?)),
+        _ => Some((middle.to_string(), last.parse().ok()?)),
+    }
+}
+
+/// `error[E0308]: mismatched types` followed by `--> src/main.rs:10:5`, and test panics.
+fn parse_cargo(output: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut diagnostics = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.starts_with("error")
+            && line.contains(": ")
+            && !line.starts_with("error: could not compile")
+            && !line.starts_with("error: test failed")
+        {
+            let message = line.split_once(": ").map(|(_, m)| m).unwrap_or(line).to_string();
+            let location = lines[i + 1..]
+                .iter()
+                .take(3)
+                .find_map(|l| l.trim_start().strip_prefix("--> "))
+                .and_then(split_location);
+            diagnostics.push(Diagnostic {
+                file: location.as_ref().map(|(f, _)| f.clone()),
+                line: location.map(|(_, l)| l),
+                message,
+            });
+        } else if let Some(rest) = line.strip_prefix("thread '") {
+            // thread 'tests::it_works' panicked at src/lib.rs:10:5:
+            let Some((test, location)) = rest.split_once("' panicked at ") else {
+                continue;
+            };
+            let location = split_location(location.trim_end_matches(':'));
+            let detail = lines.get(i + 1).map(|l| l.trim()).unwrap_or_default();
+            diagnostics.push(Diagnostic {
+                file: location.as_ref().map(|(f, _)| f.clone()),
+                line: location.map(|(_, l)| l),
+                message: format!("test {} panicked: {}", test, detail),
+            });
+        }
+    }
+    diagnostics
+}
+
+/// `FAILED tests/test_app.py::test_add - AssertionError: assert 3 == 4`
+fn parse_pytest(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("FAILED ").or_else(|| line.strip_prefix("ERROR "))?;
+            let (test, message) = rest.split_once(" - ").unwrap_or((rest, "failed"));
+            let (file, name) = test.split_once("::").unwrap_or((test, test));
+            Some(Diagnostic {
+                file: Some(file.to_string()),
+                line: None,
+                message: format!("{} {}", name, message),
+            })
+        })
+        .collect()
+}
+
+/// `● Suite › test name`, the first message line, and the first `(file:line:col)` frame.
+fn parse_jest(output: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut diagnostics = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let Some(title) = line.trim().strip_prefix("● ") else {
+            continue;
+        };
+        let block: Vec<&str> = lines[i + 1..]
+            .iter()
+            .take_while(|l| !l.trim().starts_with("● "))
+            .copied()
+            .collect();
+        let detail = block.iter().map(|l| l.trim()).find(|l| !l.is_empty()).unwrap_or_default();
+        let location = block
+            .iter()
+            .filter(|l| l.trim().starts_with("at ") && !l.contains("node_modules"))
+            .find_map(|l| {
+                let start = l.rfind('(')? + 1;
+                let end = l.rfind(')')?;
+                split_location(&l[start..end])
+            });
+        diagnostics.push(Diagnostic {
+            file: location.as_ref().map(|(f, _)| f.clone()),
+            line: location.map(|(_, l)| l),
+            message: format!("{}: {}", title, detail),
+        });
+    }
+    diagnostics
+}
+
+/// `src/app.ts(10,5): error TS2322: ...` and `src/app.ts:10:5 - error TS2322: ...`
+fn parse_tsc(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let error_at = line.find("error TS")?;
+            let message = line[error_at + "error ".len()..].to_string();
+            let location = line[..error_at].trim_end_matches([' ', ':', '-']);
+            let (file, line_number) = if let Some(paren) = location.find('(') {
+                let line_number = location[paren + 1..].split(',').next()?.parse().ok();
+                (location[..paren].to_string(), line_number)
+            } else {
+                let (file, line_number) = split_location(location)?;
+                (file, Some(line_number))
+            };
+            Some(Diagnostic {
+                file: Some(file),
+                line: line_number,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Pulls a compact list of errors out of build or test output from cargo, pytest, jest or tsc.
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for parser in [parse_cargo, parse_tsc, parse_pytest, parse_jest] {
+        for diagnostic in parser(output) {
+            if !diagnostics.contains(&diagnostic) {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+    diagnostics.truncate(MAX_DIAGNOSTICS);
+    diagnostics
+}
+
+/// The diagnostics as a bullet list, or the tail of the raw output if none were recognised.
+pub fn summarize(output: &str) -> String {
+    let diagnostics = parse_diagnostics(output);
+    if diagnostics.is_empty() {
+        let lines: Vec<&str> = output.lines().collect();
+        let start = lines.len().saturating_sub(FALLBACK_LINES);
+        return lines[start..].join("\n");
+    }
+    diagnostics
+        .iter()
+        .map(|d| format!("- {}", d))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(file: &str, line: Option<u32>, message: &str) -> Diagnostic {
+        Diagnostic {
+            file: Some(file.to_string()),
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_cargo_errors_and_panics() {
+        let output = "   Compiling app v0.1.0 (/work/app)
+error[E0308]: mismatched types
+  --> src/main.rs:10:5
+   |
+10 |     \"ten\"
+   |     ^^^^^ expected `u32`, found `&str`
+
+error: could not compile `app` (bin \"app\") due to 1 previous error
+thread 'tests::adds' panicked at src/lib.rs:21:9:
+assertion `left == right` failed
+error: test failed, to rerun pass `--lib`
+";
+        assert_eq!(
+            parse_diagnostics(output),
+            [
+                diagnostic("src/main.rs", Some(10), "mismatched types"),
+                diagnostic("src/lib.rs", Some(21), "test tests::adds panicked: assertion `left == right` failed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_pytest_failures() {
+        let output = "FAILED tests/test_app.py::test_add - AssertionError: assert 3 == 4
+ERROR tests/test_db.py::test_connect
+=== 1 failed, 1 error in 0.12s ===
+";
+        assert_eq!(
+            parse_diagnostics(output),
+            [
+                diagnostic("tests/test_app.py", None, "test_add AssertionError: assert 3 == 4"),
+                diagnostic("tests/test_db.py", None, "test_connect failed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_jest_failures_once() {
+        let block = "  ● Calculator › adds numbers
+
+    expect(received).toBe(expected) // Object.is equality
+
+      at Object.toBe (node_modules/expect/build/index.js:1:1)
+      at Object.<anonymous> (src/calc.test.js:5:17)
+";
+        // Jest repeats the failures in its summary
+        let output = format!("{}\nSummary of all failing tests\n{}", block, block);
+        assert_eq!(
+            parse_diagnostics(&output),
+            [diagnostic(
+                "src/calc.test.js",
+                Some(5),
+                "Calculator › adds numbers: expect(received).toBe(expected) // Object.is equality"
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_both_tsc_formats() {
+        let output = "src/app.ts(10,5): error TS2322: Type 'string' is not assignable to type 'number'.
+src/api.ts:3:1 - error TS2304: Cannot find name 'fetchUser'.
+";
+        assert_eq!(
+            parse_diagnostics(output),
+            [
+                diagnostic("src/app.ts", Some(10), "TS2322: Type 'string' is not assignable to type 'number'."),
+                diagnostic("src/api.ts", Some(3), "TS2304: Cannot find name 'fetchUser'."),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_first_errors() {
+        let output: String = (1..=30)
+            .map(|i| format!("FAILED tests/test_app.py::test_{} - assert False\n", i))
+            .collect();
+        let diagnostics = parse_diagnostics(&output);
+        assert_eq!(diagnostics.len(), MAX_DIAGNOSTICS);
+        assert_eq!(diagnostics[0].message, "test_1 assert False");
+    }
+
+    #[test]
+    fn summarizes_unknown_output_with_its_tail() {
+        let output: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        let summary = summarize(&output);
+        assert!(summary.starts_with("line 41\n"));
+        assert!(summary.ends_with("line 100"));
+
+        assert_eq!(
+            summarize("FAILED tests/test_app.py::test_add - assert 3 == 4"),
+            "- tests/test_app.py: test_add assert 3 == 4"
+        );
+    }
+}
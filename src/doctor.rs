@@ -0,0 +1,312 @@
+use crate::cli::DoctorArgs;
+use crate::git;
+use crate::providers::{Provider, Providers, OPENAI_BASE_URL};
+use crate::search::Backend;
+use crate::voice;
+use colored::*;
+use reqwest::Method;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+// Value the .env template ships with, left in place until the user edits it
+const PLACEHOLDER: &str = "<API_KEY>";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn add(&mut self, name: &str, status: Status, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        });
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == Status::Fail)
+    }
+
+    pub fn print(&self) {
+        println!("{}", "Colossus doctor".bright_cyan());
+        for check in &self.checks {
+            let (icon, name) = match check.status {
+                Status::Ok => ("✅", check.name.bright_green()),
+                Status::Warn => ("⚠️ ", check.name.yellow()),
+                Status::Fail => ("❌", check.name.bright_red()),
+            };
+            println!("{} {:<22} {}", icon, name, check.detail);
+        }
+        let failures = self.checks.iter().filter(|c| c.status == Status::Fail).count();
+        let warnings = self.checks.iter().filter(|c| c.status == Status::Warn).count();
+        println!("{} failed, {} warnings\n", failures, warnings);
+    }
+}
+
+// First line of `<binary> --version`, or None when it can't be run
+async fn version(binary: &str) -> Option<String> {
+    let output = Command::new(binary).arg("--version").output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.lines().next().unwrap_or_default().trim().to_string())
+}
+
+async fn check_binaries(report: &mut Report) {
+    for (binary, purpose) in [
+        ("git", "version control and worktrees"),
+        ("aider", "every agent role"),
+        ("make", "build and test targets"),
+    ] {
+        match version(binary).await {
+            Some(version) => report.add(binary, Status::Ok, version),
+            None => report.add(binary, Status::Fail, format!("not found on PATH, needed for {}", purpose)),
+        }
+    }
+}
+
+/// The key's value, or an explanation of why it can't be used.
+fn api_key(name: &str) -> Result<String, String> {
+    match std::env::var(name) {
+        Ok(value) if value.trim().is_empty() => Err("is empty".to_string()),
+        Ok(value) if value.contains(PLACEHOLDER) => Err(format!("still set to the {} placeholder from .env", PLACEHOLDER)),
+        Ok(value) => Ok(value),
+        Err(_) => Err("is not set in the environment or .env".to_string()),
+    }
+}
+
+// The environment variable holding the key aider needs for `model`
+fn code_model_key(model: &str) -> &'static str {
+    let model = model.to_lowercase();
+    if model.contains("claude") || model.starts_with("anthropic/") || model == "sonnet" || model == "opus" || model == "haiku" {
+        "ANTHROPIC_API_KEY"
+    } else if model.contains("deepseek") {
+        "DEEPSEEK_API_KEY"
+    } else {
+        "OPENAI_API_KEY"
+    }
+}
+
+// An unreachable API is only a warning, the key may well be fine once the network is back
+fn unreachable(provider: &Provider, e: reqwest::Error) -> (Status, String) {
+    (Status::Warn, format!("could not reach {} to validate it: {}", provider.base_url, e))
+}
+
+/// Lists the models the provider's key can use, which also proves the key is valid.
+async fn list_models(client: &reqwest::Client, provider: &Provider) -> Result<Vec<String>, (Status, String)> {
+    let response = provider
+        .request(client, Method::GET, "models")
+        .send()
+        .await
+        .map_err(|e| unreachable(provider, e))?;
+    match response.status().as_u16() {
+        401 | 403 => return Err((Status::Fail, format!("rejected by {}", provider.base_url))),
+        status if status >= 400 => return Err((Status::Warn, format!("{} answered {}", provider.base_url, status))),
+        _ => {}
+    }
+    let body: serde_json::Value = response.json().await.map_err(|e| unreachable(provider, e))?;
+    Ok(body["data"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m["id"].as_str().map(|id| id.to_string()))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Sends an empty chat request: an invalid key gets 401, a valid one a 400 for the missing fields.
+async fn chat_key_valid(client: &reqwest::Client, provider: &Provider) -> Result<(), (Status, String)> {
+    let response = provider
+        .request(client, Method::POST, "chat/completions")
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .map_err(|e| unreachable(provider, e))?;
+    match response.status().as_u16() {
+        401 | 403 => Err((Status::Warn, format!("rejected by {}", provider.base_url))),
+        _ => Ok(()),
+    }
+}
+
+async fn check_api_keys(report: &mut Report, args: &DoctorArgs, providers: &Providers, known_model: bool) {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+
+    let realtime = &providers.realtime;
+    let key_name = realtime.api_key_env.as_str();
+    // Headless runs never open a voice session, so there the key only matters to aider
+    let needed = !args.headless || args.code_model.as_deref().is_some_and(|m| code_model_key(m) == key_name);
+    let severity = |status: Status, problem: String| match status {
+        Status::Fail if !needed => (Status::Warn, format!("{}, only voice sessions need it", problem)),
+        status => (status, problem),
+    };
+    match api_key(key_name) {
+        Err(problem) => {
+            let (status, problem) = severity(Status::Fail, format!("{} {}", key_name, problem));
+            report.add(key_name, status, problem)
+        }
+        Ok(_) if args.offline => report.add(key_name, Status::Ok, "set (not validated, --offline)"),
+        Ok(_) => match list_models(&client, realtime).await {
+            Err((status, problem)) => {
+                let (status, problem) = severity(status, problem);
+                report.add(key_name, status, problem)
+            }
+            Ok(models) => {
+                report.add(key_name, Status::Ok, format!("valid for {}", realtime.base_url));
+                if !args.headless && known_model {
+                    if models.iter().any(|m| m == &realtime.model) {
+                        report.add("voice model", Status::Ok, realtime.model.clone());
+                    } else {
+                        report.add(
+                            "voice model",
+                            Status::Warn,
+                            format!("{} is not available to this key, only text chat will work", realtime.model),
+                        );
+                    }
+                }
+                // aider calls OpenAI itself, so only OpenAI's list says anything about its model,
+                // and aider also accepts aliases that aren't in it
+                let openai_code_model = args
+                    .code_model
+                    .as_ref()
+                    .filter(|m| code_model_key(m) == "OPENAI_API_KEY" && !m.contains('/'))
+                    .filter(|_| realtime.base_url.trim_end_matches('/') == OPENAI_BASE_URL);
+                if let Some(code_model) = openai_code_model {
+                    if models.iter().any(|m| m == code_model) {
+                        report.add("code model", Status::Ok, code_model.clone());
+                    } else {
+                        report.add(
+                            "code model",
+                            Status::Warn,
+                            format!("{} is not listed for this key, fine if it is an aider alias", code_model),
+                        );
+                    }
+                }
+            }
+        },
+    }
+
+    // Text chat only needs its own check when it goes somewhere else than the voice API
+    let chat = &providers.chat;
+    if !args.headless && !args.offline && (chat.base_url != realtime.base_url || chat.api_key_env != realtime.api_key_env) {
+        match list_models(&client, chat).await {
+            Ok(_) => report.add("text chat", Status::Ok, format!("{} at {}", chat.model, chat.base_url)),
+            Err((_, problem)) => report.add("text chat", Status::Warn, format!("{}, text chat may not work", problem)),
+        }
+    }
+
+    let search = &providers.search;
+    let key_name = search.api_key_env.as_str();
+    match providers.search_options.backend {
+        Backend::Local => report.add("web search", Status::Ok, "project files only"),
+        Backend::Searxng => report.add("web search", Status::Ok, format!("SearxNG at {}", search.base_url)),
+        Backend::Perplexity => match api_key(key_name) {
+            Err(problem) => report.add(
+                key_name,
+                Status::Warn,
+                format!("{} {}, web search will only find project files", key_name, problem),
+            ),
+            Ok(_) if args.offline => report.add(key_name, Status::Ok, "set (not validated, --offline)"),
+            Ok(_) => match chat_key_valid(&client, search).await {
+                Ok(()) => report.add(key_name, Status::Ok, format!("valid for {}", search.base_url)),
+                Err((status, problem)) => report.add(key_name, status, format!("{}, web search may not work", problem)),
+            },
+        },
+    }
+
+    // aider picks its key from the model name, so only the key the code model needs matters
+    match &args.code_model {
+        Some(model) if code_model_key(model) != "OPENAI_API_KEY" => {
+            let key = code_model_key(model);
+            match api_key(key) {
+                Ok(_) => report.add("code model", Status::Ok, format!("{} with {}", model, key)),
+                Err(problem) => report.add("code model", Status::Fail, format!("{} needs {}, which {}", model, key, problem)),
+            }
+        }
+        Some(_) => {}
+        None => report.add("code model", Status::Ok, "aider default"),
+    }
+}
+
+// Catches typos before the browser gets an opaque error creating a session
+fn check_voice_model(report: &mut Report, providers: &Providers) -> bool {
+    match voice::check_model(&providers.realtime.model, &providers.realtime) {
+        Ok(_) => true,
+        Err(problem) => {
+            report.add("voice model", Status::Fail, problem);
+            false
+        }
+    }
+}
+
+fn check_port(report: &mut Report, port: u16) {
+    match TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port))) {
+        Ok(_) => report.add("port", Status::Ok, format!("{} is free", port)),
+        Err(e) => report.add("port", Status::Fail, format!("{} is unavailable: {}", port, e)),
+    }
+}
+
+async fn check_project(report: &mut Report, project_dir: &Path) {
+    if !project_dir.join(".git").exists() {
+        report.add("git repository", Status::Fail, "no .git directory, run `colossus init` or `git init`");
+    } else {
+        match git::git(project_dir, &["status", "--porcelain"]).await {
+            Ok(status) if status.trim().is_empty() => report.add("git repository", Status::Ok, "clean"),
+            Ok(status) => report.add(
+                "git repository",
+                Status::Warn,
+                format!("{} uncommitted changes, agents will commit on top of them", status.lines().count()),
+            ),
+            Err(e) => report.add("git repository", Status::Fail, e),
+        }
+    }
+
+    for (file, purpose) in [("CONTEXT.md", "the files aider loads"), ("Makefile", "the build and test targets")] {
+        if project_dir.join(file).exists() {
+            report.add(file, Status::Ok, "present");
+        } else {
+            // The planning roles can start without them, development can't
+            report.add(
+                file,
+                Status::Warn,
+                format!("missing, it defines {}; run `colossus init` to create it", purpose),
+            );
+        }
+    }
+}
+
+/// Checks everything `serve` needs: binaries, API keys, the port and the project itself.
+pub async fn run(args: &DoctorArgs, providers: &Providers) -> Report {
+    let mut report = Report::default();
+    check_binaries(&mut report).await;
+    let known_model = args.headless || check_voice_model(&mut report, providers);
+    check_api_keys(&mut report, args, providers, known_model).await;
+    if !args.headless {
+        check_port(&mut report, args.port);
+    }
+    check_project(&mut report, Path::new(&args.project_dir)).await;
+    report
+}
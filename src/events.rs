@@ -0,0 +1,76 @@
+use crate::jobs::Job;
+use crate::questions::Question;
+use crate::repo_lock::Holder;
+use crate::AppStateWithDir;
+use axum::extract::State;
+use axum::response::sse::{self, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+// Events a slow browser can fall behind by before it misses some
+const CAPACITY: usize = 256;
+
+/// Something the browser should hear about without polling.
+#[derive(Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Job { job: Job },
+    /// Who is working on the repository, empty when no one is
+    RepoLock { holders: Vec<Holder> },
+    /// Progress the voice assistant should mention, e.g. a finished task
+    Commentary { message: String },
+    /// The project context of voice session instructions changed
+    ProjectContext { context: String },
+    /// QUESTIONS.md was updated
+    Questions { questions: Vec<Question> },
+}
+
+/// How much progress the voice assistant talks about, from nothing to everything.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Off,
+    /// Finished tasks and calls for a human
+    Important,
+    /// Also started tasks and builds that keep failing
+    Normal,
+    /// Also updated planning documents
+    Verbose,
+}
+
+impl Verbosity {
+    pub fn parse(name: &str) -> Verbosity {
+        match name {
+            "off" => Verbosity::Off,
+            "important" => Verbosity::Important,
+            "verbose" => Verbosity::Verbose,
+            _ => Verbosity::Normal,
+        }
+    }
+}
+
+pub fn channel() -> broadcast::Sender<Event> {
+    broadcast::channel(CAPACITY).0
+}
+
+/// `GET /events`: every event as a server-sent event with a JSON payload.
+pub async fn stream_events(
+    State(state): State<Arc<AppStateWithDir>>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let events = stream::unfold(state.events.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let data = serde_json::to_string(&event).unwrap_or_default();
+                    return Some((Ok(sse::Event::default().data(data)), receiver));
+                }
+                // A lagging browser skips what it missed, it can still poll for the rest
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
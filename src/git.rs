@@ -0,0 +1,200 @@
+use std::path::Path;
+use tokio::process::Command;
+
+pub enum MergeOutcome {
+    Merged,
+    Conflict(Vec<String>),
+}
+
+async fn run_git(dir: &Path, args: &[&str], index_file: Option<&Path>) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir).args(args);
+    if let Some(index_file) = index_file {
+        cmd.env("GIT_INDEX_FILE", index_file);
+    }
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git {}: {}", args.join(" "), e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// Runs git in `dir`, returning stdout on success and stderr on failure.
+pub async fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    run_git(dir, args, None).await
+}
+
+pub async fn current_branch(dir: &Path) -> Result<String, String> {
+    git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])
+        .await
+        .map(|branch| branch.trim().to_string())
+}
+
+/// Keeps a directory inside the repository out of `git status` without touching .gitignore.
+/// The exclude file lives in the common git dir, so it also covers every worktree.
+pub async fn exclude_path(dir: &Path, pattern: &str) -> Result<(), String> {
+    let common_dir = git(dir, &["rev-parse", "--git-common-dir"]).await?;
+    let info_dir = dir.join(common_dir.trim()).join("info");
+    let exclude_path = info_dir.join("exclude");
+    let existing = std::fs::read_to_string(&exclude_path).unwrap_or_default();
+    if existing.lines().any(|line| line.trim() == pattern) {
+        return Ok(());
+    }
+    std::fs::create_dir_all(&info_dir).map_err(|e| format!("Failed to create {}: {}", info_dir.display(), e))?;
+    let mut content = existing;
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(pattern);
+    content.push('\n');
+    std::fs::write(&exclude_path, content).map_err(|e| format!("Failed to update git exclude: {}", e))
+}
+
+/// Keeps Colossus' run state in `.colossus/` (run log, repository lock, review files) out of
+/// commits, diffs and working tree snapshots. Called before any role starts.
+pub async fn exclude_run_state(dir: &Path) {
+    if let Err(e) = exclude_path(dir, "/.colossus/").await {
+        eprintln!("Failed to keep .colossus/ out of git: {}", e);
+    }
+}
+
+/// Creates a worktree on a fresh branch, or reuses it if a previous run left it behind.
+pub async fn add_worktree(project_dir: &Path, worktree: &Path, branch: &str, base: &str) -> Result<(), String> {
+    if worktree.exists() {
+        return Ok(());
+    }
+    let worktree = worktree.to_string_lossy();
+    if git(project_dir, &["rev-parse", "--verify", "--quiet", branch]).await.is_ok() {
+        git(project_dir, &["worktree", "add", &worktree, branch]).await?;
+    } else {
+        git(project_dir, &["worktree", "add", "-b", branch, &worktree, base]).await?;
+    }
+    Ok(())
+}
+
+pub async fn remove_worktree(project_dir: &Path, worktree: &Path, branch: &str) -> Result<(), String> {
+    git(project_dir, &["worktree", "remove", "--force", &worktree.to_string_lossy()]).await?;
+    git(project_dir, &["branch", "-D", branch]).await?;
+    Ok(())
+}
+
+/// Stages everything and commits, returning false when there was nothing to commit.
+pub async fn commit_all(dir: &Path, message: &str) -> Result<bool, String> {
+    git(dir, &["add", "-A"]).await?;
+    if git(dir, &["diff", "--cached", "--quiet"]).await.is_ok() {
+        return Ok(false);
+    }
+    git(dir, &["commit", "-m", message]).await?;
+    Ok(true)
+}
+
+/// Commits only the given paths, skipping any that do not exist, and returns false when
+/// none of them changed. Anything else staged stays staged and out of the commit.
+pub async fn commit_paths(dir: &Path, paths: &[&str], message: &str) -> Result<bool, String> {
+    let existing: Vec<&str> = paths.iter().copied().filter(|p| dir.join(p).exists()).collect();
+    if existing.is_empty() {
+        return Ok(false);
+    }
+    let mut add_args = vec!["add", "--"];
+    add_args.extend(&existing);
+    git(dir, &add_args).await?;
+    let mut diff_args = vec!["diff", "--cached", "--quiet", "--"];
+    diff_args.extend(&existing);
+    if git(dir, &diff_args).await.is_ok() {
+        return Ok(false);
+    }
+    let mut commit_args = vec!["commit", "-m", message, "--"];
+    commit_args.extend(&existing);
+    git(dir, &commit_args).await?;
+    Ok(true)
+}
+
+pub async fn conflicted_files(dir: &Path) -> Vec<String> {
+    git(dir, &["diff", "--name-only", "--diff-filter=U"])
+        .await
+        .map(|out| out.lines().map(|l| l.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Merges `branch` into the branch checked out in `dir`. Conflicting merges are left in
+/// progress so the caller can either resolve or abort them.
+pub async fn merge_branch(dir: &Path, branch: &str) -> Result<MergeOutcome, String> {
+    match git(dir, &["merge", "--no-ff", "--no-edit", branch]).await {
+        Ok(_) => Ok(MergeOutcome::Merged),
+        Err(error) => {
+            let conflicts = conflicted_files(dir).await;
+            if conflicts.is_empty() {
+                Err(error)
+            } else {
+                Ok(MergeOutcome::Conflict(conflicts))
+            }
+        }
+    }
+}
+
+pub async fn abort_merge(dir: &Path) {
+    if let Err(e) = git(dir, &["merge", "--abort"]).await {
+        eprintln!("{}", e);
+    }
+}
+
+// The hash git uses for a tree with nothing in it, for diffing repositories without commits
+pub const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+pub async fn head_commit(dir: &Path) -> String {
+    git(dir, &["rev-parse", "HEAD"])
+        .await
+        .map(|sha| sha.trim().to_string())
+        .unwrap_or_else(|_| EMPTY_TREE.to_string())
+}
+
+pub async fn merge_base(dir: &Path, a: &str, b: &str) -> Result<String, String> {
+    git(dir, &["merge-base", a, b]).await.map(|sha| sha.trim().to_string())
+}
+
+/// Everything that changed since `base`, committed or not, including new files. Diffs
+/// against a snapshot of the working directory, so the real index is left alone.
+pub async fn diff_since(dir: &Path, base: &str) -> Result<String, String> {
+    let tree = snapshot_tree(dir)
+        .await
+        .ok_or_else(|| "Failed to snapshot the working directory".to_string())?;
+    git(dir, &["diff", base, &tree]).await
+}
+
+/// Tree hash of the working directory as it is right now, including uncommitted and new
+/// files. Uses a scratch index so the real one is left alone.
+pub async fn snapshot_tree(dir: &Path) -> Option<String> {
+    let index = git(dir, &["rev-parse", "--git-path", "colossus-snapshot-index"]).await.ok()?;
+    let index = dir.join(index.trim());
+
+    if run_git(dir, &["read-tree", "HEAD"], Some(&index)).await.is_err() {
+        run_git(dir, &["read-tree", "--empty"], Some(&index)).await.ok()?;
+    }
+    run_git(dir, &["add", "-A"], Some(&index)).await.ok()?;
+    run_git(dir, &["write-tree"], Some(&index))
+        .await
+        .ok()
+        .map(|tree| tree.trim().to_string())
+}
+
+/// Whether the working directory differs from `base` in any way.
+pub async fn has_changes_since(dir: &Path, base: &str) -> bool {
+    let base_tree = git(dir, &["rev-parse", &format!("{}^{{tree}}", base)])
+        .await
+        .map(|tree| tree.trim().to_string())
+        .unwrap_or_else(|_| EMPTY_TREE.to_string());
+    match snapshot_tree(dir).await {
+        Some(tree) => tree != base_tree,
+        None => true,
+    }
+}
@@ -0,0 +1,226 @@
+use crate::architect::architect_loop;
+use crate::cli::{DoctorArgs, RunArgs};
+use crate::developer::developer_loop;
+use crate::doctor;
+use crate::git;
+use crate::events::{self, Verbosity};
+use crate::jobs::Jobs;
+use crate::repo_lock::RepoLock;
+use crate::session::ProjectBriefing;
+use crate::providers::Providers;
+use crate::product_manager::product_manager_loop;
+use crate::project_manager::project_manager_loop;
+use crate::tasks;
+use crate::tester::tester_loop;
+use crate::usage::UsageTracker;
+use crate::voice::VoiceSettings;
+use crate::workers::{developer_worker_loop, WorkerPool};
+use crate::{ActivityMode, AppStateWithDir};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration, Instant};
+
+// Exit codes for batch jobs
+const EXIT_DONE: i32 = 0;
+const EXIT_SETUP_FAILED: i32 = 1;
+const EXIT_NEEDS_HUMAN: i32 = 2;
+const EXIT_BUDGET_EXHAUSTED: i32 = 3;
+const EXIT_TIMED_OUT: i32 = 4;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const PLANNING_ROLES: [&str; 4] = ["product_manager", "architect", "project_manager", "tester"];
+
+// Whether `output` exists and is at least as new as every input that exists
+fn up_to_date(project_dir: &Path, output: &str, inputs: &[&str]) -> bool {
+    let modified = |file: &str| fs::metadata(project_dir.join(file)).and_then(|m| m.modified()).ok();
+    let Some(output_modified) = modified(output) else {
+        return false;
+    };
+    inputs
+        .iter()
+        .filter_map(|input| modified(input))
+        .all(|input_modified| input_modified <= output_modified)
+}
+
+/// Planning has converged once every role's document is newer than what it is derived
+/// from, the same checks the role loops use to decide whether to run.
+fn planning_converged(project_dir: &Path) -> bool {
+    up_to_date(project_dir, "PROJECT.md", &["TRANSCRIPT.md"])
+        && up_to_date(project_dir, "ARCHITECTURE.md", &["PROJECT.md"])
+        && up_to_date(project_dir, "TASKS.md", &["PROJECT.md", "ARCHITECTURE.md"])
+        && up_to_date(project_dir, "TEST_STRATEGY.md", &["TASKS.md", "ARCHITECTURE.md"])
+}
+
+fn spawn_planning_roles(state: &Arc<AppStateWithDir>) {
+    let project_dir = state.project_dir.clone();
+    let shutdown = state.shutdown_signal.clone();
+    tokio::spawn(product_manager_loop(project_dir.clone(), shutdown.clone(), state.clone()));
+    tokio::spawn(architect_loop(project_dir.clone(), shutdown.clone(), state.clone()));
+    tokio::spawn(project_manager_loop(project_dir.clone(), shutdown.clone(), state.clone()));
+    tokio::spawn(tester_loop(project_dir, shutdown, state.clone()));
+}
+
+fn spawn_developers(state: &Arc<AppStateWithDir>, workers: usize) {
+    let project_dir = state.project_dir.clone();
+    let shutdown = state.shutdown_signal.clone();
+    if workers > 1 {
+        let pool = Arc::new(WorkerPool::new());
+        for worker_id in 1..=workers {
+            tokio::spawn(developer_worker_loop(
+                worker_id,
+                project_dir.clone(),
+                shutdown.clone(),
+                state.clone(),
+                pool.clone(),
+            ));
+        }
+    } else {
+        tokio::spawn(developer_loop(project_dir, shutdown, state.clone()));
+    }
+}
+
+/// Why the run can't go on, as an exit code and a message.
+async fn stop_reason(state: &AppStateWithDir, roles: &[&str], deadline: Instant) -> Option<(i32, String)> {
+    if matches!(*state.activity_mode.lock().await, ActivityMode::ErrorNeedsHuman) {
+        let reason = state.needs_human_reason.lock().await.clone().unwrap_or_default();
+        return Some((EXIT_NEEDS_HUMAN, format!("Human intervention needed: {}", reason)));
+    }
+    for role in roles {
+        if state.usage.is_paused(role).await {
+            return Some((EXIT_BUDGET_EXHAUSTED, format!("Budget exhausted for {}", role)));
+        }
+    }
+    if Instant::now() >= deadline {
+        return Some((EXIT_TIMED_OUT, "Timed out".to_string()));
+    }
+    None
+}
+
+async fn finish(state: &AppStateWithDir, code: i32, message: &str) -> i32 {
+    *state.shutdown_signal.lock().await = true;
+    let report = state.usage.report().await;
+    if code == EXIT_DONE {
+        println!("✅ {}", message);
+    } else {
+        eprintln!("❌ {}", message);
+    }
+    println!("💸 Spent ${:.2}", report.total_cost_usd);
+    code
+}
+
+/// Runs planning to convergence and then development until every task is done,
+/// returning the process exit code.
+pub async fn run(args: &RunArgs) -> i32 {
+    let project_dir = Path::new(&args.project_dir);
+    let requirements = match fs::read_to_string(&args.requirements) {
+        Ok(requirements) => requirements,
+        Err(e) => {
+            eprintln!("Failed to read requirements '{}': {}", args.requirements, e);
+            return EXIT_SETUP_FAILED;
+        }
+    };
+
+    let providers = match Providers::load(args.providers.as_deref(), &args.project_dir) {
+        Ok(providers) => providers,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_SETUP_FAILED;
+        }
+    };
+    let report = doctor::run(
+        &DoctorArgs {
+            project_dir: args.project_dir.clone(),
+            port: 0,
+            model: None,
+            code_model: Some(args.code_model.clone()),
+            providers: args.providers.clone(),
+            offline: false,
+            headless: true,
+        },
+        &providers,
+    )
+    .await;
+    report.print();
+    if report.has_failures() {
+        return EXIT_SETUP_FAILED;
+    }
+    git::exclude_run_state(project_dir).await;
+
+    // The requirements stand in for the interview, so the product manager picks them up
+    if let Err(e) = fs::write(project_dir.join("TRANSCRIPT.md"), requirements) {
+        eprintln!("Failed to write TRANSCRIPT.md: {}", e);
+        return EXIT_SETUP_FAILED;
+    }
+
+    let events = events::channel();
+    let state = Arc::new(AppStateWithDir {
+        shutdown_signal: Arc::new(Mutex::new(false)),
+        project_dir: args.project_dir.clone(),
+        voice_settings: Mutex::new(VoiceSettings {
+            voice: String::new(),
+            model: providers.realtime.model.clone(),
+            language: "english".to_string(),
+        }),
+        providers,
+        instructions: None,
+        persona: None,
+        code_model: Some(args.code_model.clone()),
+        escalation_model: args.escalation_model.clone(),
+        activity_mode: Arc::new(Mutex::new(ActivityMode::Planning)),
+        review: !args.no_review,
+        tdd: args.tdd,
+        needs_human_reason: Mutex::new(None),
+        usage: UsageTracker::new(args.budget, args.role_budgets.iter().cloned().collect()),
+        role_states: Mutex::new(BTreeMap::new()),
+        chat_history: Mutex::new(Vec::new()),
+        repo_lock: RepoLock::new(&args.project_dir, events.clone()),
+        jobs: Jobs::default(),
+        events,
+        commentary: Verbosity::Off,
+        briefing: ProjectBriefing::default(),
+    });
+    let deadline = Instant::now() + Duration::from_secs(args.timeout * 60);
+    let planning_deadline = Instant::now() + Duration::from_secs(args.planning_timeout * 60);
+
+    println!("📝 Planning from {}...", args.requirements);
+    spawn_planning_roles(&state);
+    let mut interval = time::interval(POLL_INTERVAL);
+    // Converged must hold on two polls in a row, a role may be about to pick up a change
+    let mut converged_polls = 0;
+    while converged_polls < 2 {
+        interval.tick().await;
+        if let Some((code, message)) = stop_reason(&state, &PLANNING_ROLES, deadline.min(planning_deadline)).await {
+            return finish(&state, code, &format!("Planning stopped: {}", message)).await;
+        }
+        if planning_converged(project_dir) && !tasks::read_tasks(&args.project_dir).is_empty() {
+            converged_polls += 1;
+        } else {
+            converged_polls = 0;
+        }
+    }
+
+    let total = tasks::read_tasks(&args.project_dir).len();
+    println!("✨ Planning converged with {} tasks, starting development", total);
+    *state.activity_mode.lock().await = ActivityMode::Developing;
+    spawn_developers(&state, args.workers);
+
+    let mut last_done = usize::MAX;
+    loop {
+        interval.tick().await;
+        let tasks = tasks::read_tasks(&args.project_dir);
+        let done = tasks.iter().filter(|t| t.done).count();
+        if done != last_done {
+            println!("📋 {}/{} tasks done", done, tasks.len());
+            last_done = done;
+        }
+        if !tasks.is_empty() && done == tasks.len() {
+            return finish(&state, EXIT_DONE, "All tasks done").await;
+        }
+        if let Some((code, message)) = stop_reason(&state, &["developer"], deadline).await {
+            return finish(&state, code, &format!("Development stopped: {}", message)).await;
+        }
+    }
+}
@@ -1,11 +1,142 @@
+use crate::cli::InitArgs;
+use crate::templates::{self, Template};
 use std::path::Path;
 use std::process::Command;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 
-pub fn project_init(dir: &str) -> Result<(), String> {
+/// What to do with a file the template would write that already exists.
+#[derive(Clone, Copy, PartialEq)]
+enum Existing {
+    Keep,
+    Merge,
+    Overwrite,
+    Ask,
+}
+
+/// Writes a project file, resolving a clash with an existing one according to `existing`.
+fn write_project_file(path: &Path, content: &str, existing: Existing) -> Result<bool, String> {
+    let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let choice = match existing {
+        _ if !path.exists() => Existing::Overwrite,
+        Existing::Ask => ask_existing(filename)?,
+        choice => choice,
+    };
+
+    let content = match choice {
+        Existing::Overwrite => content.to_string(),
+        Existing::Merge => {
+            let current = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", filename, e))?;
+            let merged = if filename == "Makefile" {
+                merge_makefile(&current, content)
+            } else {
+                merge_lines(&current, content)
+            };
+            println!("Merged {}", filename);
+            merged
+        }
+        _ => {
+            println!("{} already exists, keeping it (use --merge or --force to change it)", filename);
+            return Ok(false);
+        }
+    };
+    fs::write(path, content).map_err(|e| format!("Failed to create {}: {}", filename, e))?;
+    Ok(true)
+}
+
+fn ask_existing(filename: &str) -> Result<Existing, String> {
+    print!("{} already exists. [k]eep, [m]erge or [o]verwrite? [k]: ", filename);
+    io::stdout().flush().map_err(|e| e.to_string())?;
+
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .map_err(|e| format!("Failed to read input: {}", e))?;
+    Ok(match input.trim().to_lowercase().as_str() {
+        "m" | "merge" => Existing::Merge,
+        "o" | "overwrite" => Existing::Overwrite,
+        _ => Existing::Keep,
+    })
+}
+
+// Appends the lines of `new` that `current` doesn't have yet
+fn merge_lines(current: &str, new: &str) -> String {
+    let missing: Vec<&str> = new
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !current.lines().any(|l| l.trim() == line.trim()))
+        .collect();
+    if missing.is_empty() {
+        return current.to_string();
+    }
+    let mut merged = current.trim_end().to_string();
+    merged.push('\n');
+    merged.push_str(&missing.join("\n"));
+    merged.push('\n');
+    merged
+}
+
+// Appends the targets of `new` that `current` doesn't define, keeping existing recipes
+fn merge_makefile(current: &str, new: &str) -> String {
+    let defines = |makefile: &str, target: &str| {
+        makefile.lines().any(|line| line.split_once(':').is_some_and(|(t, _)| t.trim() == target))
+    };
+    let mut merged = current.trim_end().to_string();
+    let mut added = Vec::new();
+    for block in new.split("\n\n") {
+        let Some((target, _)) = block.split_once(':') else {
+            continue;
+        };
+        if target.starts_with('.') || target.contains(char::is_whitespace) || defines(current, target) {
+            continue;
+        }
+        merged.push_str("\n\n");
+        merged.push_str(block.trim_end());
+        added.push(target);
+    }
+    if !added.is_empty() {
+        merged.push_str(&format!("\n\n.PHONY: {}", added.join(" ")));
+    }
+    merged.push('\n');
+    merged
+}
+
+pub fn project_init(args: &InitArgs) -> Result<(), String> {
+    let dir = args.dir.as_str();
     let path = Path::new(dir);
     
+    // Settle the template before touching the filesystem so a non-interactive run fails cleanly
+    let mut template = match (&args.template, &args.language) {
+        (Some(template), _) if Path::new(template).is_dir() => Template::from_dir(Path::new(template))?,
+        (Some(name), _) | (None, Some(name)) => templates::find(name)?,
+        (None, None) => match templates::detect(path) {
+            Some((template, manifests)) => {
+                println!("Detected {} project from {}", template.name, manifests.join(", "));
+                template
+            }
+            None if io::stdin().is_terminal() => select_language()?,
+            None => {
+                return Err(format!(
+                    "No language given, none detected and no terminal to ask on. Pass --language with one of: {}",
+                    templates::names(&templates::available())
+                ))
+            }
+        },
+    };
+    for warning in template.fit_to_project(path) {
+        println!("⚠️  {}", warning);
+    }
+
+    let existing = if args.force {
+        Existing::Overwrite
+    } else if args.merge {
+        Existing::Merge
+    } else if io::stdin().is_terminal() {
+        Existing::Ask
+    } else {
+        Existing::Keep
+    };
+
     // Create directory if it doesn't exist
     if !path.exists() {
         fs::create_dir_all(path)
@@ -15,7 +146,7 @@ pub fn project_init(dir: &str) -> Result<(), String> {
 
     // Check for .git directory
     let git_dir = path.join(".git");
-    if !git_dir.exists() {
+    if !args.no_git && !git_dir.exists() {
         println!("No git repository found, initializing one...");
         
         // Initialize git repository
@@ -32,7 +163,7 @@ pub fn project_init(dir: &str) -> Result<(), String> {
         println!("Git repository initialized successfully");
     }
 
-    // Check for .env file
+    // Check for .env file, never overwritten since it holds the user's keys
     let env_path = path.join(".env");
     if !env_path.exists() {
         println!("No .env file found, creating template...");
@@ -53,125 +184,112 @@ ANTHROPIC_API_KEY=<API_KEY>
         println!("Please edit .env and add your API keys");
     }
 
-    // Ask for programming language preference
-    let language = select_language(dir)?;
-    println!("Selected language: {}", language);
+    // Seed PROJECT.md with the project name
+    let name = args.name.clone().unwrap_or_else(|| {
+        fs::canonicalize(path)
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+            .unwrap_or_else(|| "project".to_string())
+    });
+    let project_path = path.join("PROJECT.md");
+    if !project_path.exists() {
+        write_project_file(&project_path, &format!("# {}\n", name), Existing::Keep)?;
+    }
+
+    apply_template(path, &template, existing)?;
+    println!("Applied template: {}", template.name);
     
-    println!("Initialized project in '{}'", dir);
+    println!("Initialized project '{}' in '{}'", name, dir);
     println!("\nTo start the Colossus server, run:");
     println!("cd {} && colossus serve", dir);
     Ok(())
 }
 
-fn create_language_context(dir: &str, language: &str) -> Result<(), String> {
-    let path = Path::new(dir);
-    let context_path = path.join("CONTEXT.md");
-    
-    let context_content = match language {
-        "Rust" => r#"/add TASKS.md
-/read-only ARCHITECTURE.md
-/read-only PROJECT.md
-/read-only TEST_STRATEGY.md
-/read-only Makefile
-/add src/**/*.rs
-/add tests/**/*.rs
-/add Cargo.toml"#,
-        
-        "Python" => r#"/add TASKS.md
-/read-only ARCHITECTURE.md
-/read-only PROJECT.md
-/read-only TEST_STRATEGY.md
-/read-only Makefile
-/add **/*.py
-/add requirements.txt
-/add pyproject.toml"#,
-        
-        "JavaScript" => r#"/add TASKS.md
-/read-only ARCHITECTURE.md
-/read-only PROJECT.md
-/read-only TEST_STRATEGY.md
-/read-only Makefile
-/add package.json
-/add **/*.js
-/add **/*.css
-/add **/*.html
-/add jest.config.js"#,
-        
-        "TypeScript" => r#"/add TASKS.md
-/read-only ARCHITECTURE.md
-/read-only PROJECT.md
-/read-only TEST_STRATEGY.md
-/read-only Makefile
-/add package.json
-/add tsconfig.json
-/add **/*.ts
-/add **/*.tsx
-/add **/*.css
-/add **/*.html
-/add jest.config.ts"#,
-        
-        _ => return Err("Unsupported language".to_string()),
-    };
-    
-    fs::write(&context_path, context_content)
-        .map_err(|e| format!("Failed to create CONTEXT.md: {}", e))?;
-
-    // Create language-specific Makefile
-    let makefile_path = path.join("Makefile");
-    let makefile_content = match language {
-        "Rust" => r#".PHONY: build test
-
-build:
-	cargo build
-
-test:
-	cargo test"#,
-        
-        "Python" => r#".PHONY: build test
-
-build:
-	python -m pip install -r requirements.txt
-
-test:
-	python -m pytest"#,
-        
-        "JavaScript" => r#".PHONY: build test
-
-build:
-	npm install
-	npm run build
-
-test:
-	npm test"#,
-        
-        "TypeScript" => r#".PHONY: build test
+/// Writes CONTEXT.md, the Makefile, .gitignore and starter files from a template.
+fn apply_template(path: &Path, template: &Template, existing: Existing) -> Result<(), String> {
+    if let Some(context) = template.context() {
+        write_project_file(&path.join("CONTEXT.md"), &context, existing)?;
+    }
+    if let Some(makefile) = template.makefile() {
+        write_project_file(&path.join("Makefile"), &makefile, existing)?;
+    }
+    // Starter files are never merged, only replaced with --force
+    let force = existing == Existing::Overwrite;
+    let starter = if force { Existing::Overwrite } else { Existing::Keep };
+    if !template.gitignore.is_empty() {
+        update_gitignore(path, &template.gitignore)?;
+    }
+    for (file, content) in &template.files {
+        let target = path.join(file);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create '{}': {}", parent.display(), e))?;
+        }
+        write_project_file(&target, content, starter)?;
+    }
+    if let Some(files_dir) = &template.files_dir {
+        copy_template_dir(files_dir, path, force)?;
+    }
+    Ok(())
+}
 
-build:
-	npm install
-	npm run build
+// Appends the template's ignore patterns that aren't in .gitignore yet
+fn update_gitignore(path: &Path, patterns: &[String]) -> Result<(), String> {
+    let gitignore_path = path.join(".gitignore");
+    let existing = fs::read_to_string(&gitignore_path).unwrap_or_default();
+    let missing: Vec<&str> = patterns
+        .iter()
+        .map(|p| p.as_str())
+        .filter(|p| !existing.lines().any(|line| line.trim() == *p))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
 
-test:
-	npm test"#,
-        
-        _ => return Err("Unsupported language".to_string()),
-    };
-    
-    fs::write(&makefile_path, makefile_content)
-        .map_err(|e| format!("Failed to create Makefile: {}", e))?;
+    let mut content = existing;
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&missing.join("\n"));
+    content.push('\n');
+    fs::write(&gitignore_path, content).map_err(|e| format!("Failed to update .gitignore: {}", e))
+}
 
-    println!("Created CONTEXT.md and Makefile for {} development", language);
+fn copy_template_dir(from: &Path, to: &Path, force: bool) -> Result<(), String> {
+    let entries = fs::read_dir(from)
+        .map_err(|e| format!("Failed to read template '{}': {}", from.display(), e))?;
+    for entry in entries.flatten() {
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        if source.is_dir() {
+            fs::create_dir_all(&target)
+                .map_err(|e| format!("Failed to create '{}': {}", target.display(), e))?;
+            copy_template_dir(&source, &target, force)?;
+        } else {
+            let content = fs::read(&source)
+                .map_err(|e| format!("Failed to read '{}': {}", source.display(), e))?;
+            if target.exists() && !force {
+                println!("{} already exists, keeping it (use --force to overwrite)", target.display());
+                continue;
+            }
+            fs::write(&target, content)
+                .map_err(|e| format!("Failed to write '{}': {}", target.display(), e))?;
+        }
+    }
     Ok(())
 }
 
-fn select_language(dir: &str) -> Result<String, String> {
-    let languages = vec!["Rust", "Python", "JavaScript", "TypeScript"];
-    
+fn select_language() -> Result<Template, String> {
+    let mut templates = templates::available();
     println!("\nSelect your preferred programming language:");
-    for (i, lang) in languages.iter().enumerate() {
-        println!("{}. {}", i + 1, lang);
+    for (i, template) in templates.iter().enumerate() {
+        println!("{}. {}", i + 1, template.name);
     }
     
-    print!("Enter the number (1-4): ");
+    print!("Enter the number (1-{}): ", templates.len());
     io::stdout().flush().map_err(|e| e.to_string())?;
     
     let mut input = String::new();
@@ -180,16 +298,11 @@ fn select_language(dir: &str) -> Result<String, String> {
         .map_err(|e| format!("Failed to read input: {}", e))?;
     
     let selection = input.trim().parse::<usize>()
-        .map_err(|_| "Please enter a valid number (1-4)".to_string())?;
+        .map_err(|_| format!("Please enter a valid number (1-{})", templates.len()))?;
     
-    if selection < 1 || selection > languages.len() {
-        return Err(format!("Please enter a number between 1 and {}", languages.len()));
+    if selection < 1 || selection > templates.len() {
+        return Err(format!("Please enter a number between 1 and {}", templates.len()));
     }
     
-    let selected_lang = languages[selection - 1].to_string();
-    
-    // Create context file based on selected language
-    create_language_context(dir, &selected_lang)?;
-    
-    Ok(selected_lang)
+    Ok(templates.swap_remove(selection - 1))
 }
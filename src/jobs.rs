@@ -0,0 +1,194 @@
+use crate::events::Event;
+use crate::{code_model_name, AppStateWithDir};
+use chrono::Local;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+// Finished jobs kept for polling, the oldest are dropped beyond this
+const MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    ChangeCode,
+    Question,
+}
+
+impl JobKind {
+    /// The role the job's aider usage and budget belong to.
+    pub fn role(&self) -> &'static str {
+        match self {
+            JobKind::ChangeCode => "change_code",
+            JobKind::Question => "question",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Waiting for the repository while another agent writes to it
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Job {
+    pub id: u64,
+    pub kind: JobKind,
+    pub request: String,
+    pub context: Option<String>,
+    pub status: JobStatus,
+    /// aider's output once done
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub submitted_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+impl Job {
+    fn finished(&self) -> bool {
+        matches!(self.status, JobStatus::Done | JobStatus::Failed)
+    }
+}
+
+/// Code changes and questions submitted from the browser, run in the background.
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    next_id: AtomicU64,
+}
+
+fn now() -> String {
+    Local::now().to_rfc3339()
+}
+
+impl Jobs {
+    pub async fn list(&self) -> Vec<Job> {
+        self.jobs.lock().await.values().cloned().collect()
+    }
+
+    pub async fn get(&self, id: u64) -> Option<Job> {
+        self.jobs.lock().await.get(&id).cloned()
+    }
+
+    async fn insert(&self, kind: JobKind, request: &str, context: Option<String>) -> Job {
+        let job = Job {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst) + 1,
+            kind,
+            request: request.to_string(),
+            context,
+            status: JobStatus::Queued,
+            output: None,
+            error: None,
+            submitted_at: now(),
+            started_at: None,
+            finished_at: None,
+        };
+        let mut jobs = self.jobs.lock().await;
+        jobs.insert(job.id, job.clone());
+
+        let finished: Vec<u64> = jobs.values().filter(|j| j.finished()).map(|j| j.id).collect();
+        for id in finished.iter().take(finished.len().saturating_sub(MAX_FINISHED_JOBS)) {
+            jobs.remove(id);
+        }
+        job
+    }
+
+    async fn update(&self, id: u64, change: impl FnOnce(&mut Job)) -> Option<Job> {
+        let mut jobs = self.jobs.lock().await;
+        let job = jobs.get_mut(&id)?;
+        change(job);
+        Some(job.clone())
+    }
+}
+
+/// Queues a job and returns it right away; its progress is published as events.
+pub async fn submit(state: &Arc<AppStateWithDir>, kind: JobKind, request: &str, context: Option<String>) -> Job {
+    let job = state.jobs.insert(kind, request, context).await;
+    state.emit(Event::Job { job: job.clone() });
+    tokio::spawn(run(state.clone(), job.id));
+    job
+}
+
+async fn set(state: &AppStateWithDir, id: u64, change: impl FnOnce(&mut Job)) -> Option<Job> {
+    let job = state.jobs.update(id, change).await?;
+    state.emit(Event::Job { job: job.clone() });
+    Some(job)
+}
+
+async fn run(state: Arc<AppStateWithDir>, id: u64) {
+    let Some(kind) = state.jobs.get(id).await.map(|job| job.kind) else {
+        return;
+    };
+    // Questions don't edit, so they only wait for writers
+    let holder = format!("job {} ({})", id, kind.role());
+    let _repo = match kind {
+        JobKind::Question => state.repo_lock.read(&holder).await,
+        JobKind::ChangeCode => state.repo_lock.write(&holder).await,
+    };
+    let Some(job) = set(&state, id, |job| {
+        job.status = JobStatus::Running;
+        job.started_at = Some(now());
+    })
+    .await
+    else {
+        return;
+    };
+    println!("🧰 Running job {} ({}): {}", job.id, job.kind.role(), job.request);
+
+    let result = run_aider(&state, &job).await;
+    set(&state, id, |job| {
+        job.finished_at = Some(now());
+        match result {
+            Ok(output) => {
+                job.status = JobStatus::Done;
+                job.output = Some(output);
+            }
+            Err(error) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(error);
+            }
+        }
+    })
+    .await;
+}
+
+async fn run_aider(state: &AppStateWithDir, job: &Job) -> Result<String, String> {
+    let mut cmd = Command::new("aider");
+    cmd.current_dir(&state.project_dir)
+        .arg("--no-suggest-shell-commands")
+        .arg("--yes-always")
+        .arg("--message")
+        .arg(&job.request);
+    if let Some(model) = &state.code_model {
+        cmd.arg("--model").arg(model);
+    }
+    if let Some(context) = &job.context {
+        cmd.arg("--load").arg(context);
+    }
+    if job.kind == JobKind::Question {
+        cmd.arg("--chat-mode").arg("ask").arg("--no-auto-commits");
+    }
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute aider: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Aider command failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    state
+        .usage
+        .record_aider(job.kind.role(), None, code_model_name(state), &stdout)
+        .await;
+    Ok(stdout)
+}
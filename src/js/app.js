@@ -11,17 +11,37 @@ async function pollCurrentMode() {
     if (mode !== currentMode) {
       currentMode = mode;
       updateModeToggle();
+      if (isConnected && sessionPersona === "auto") {
+        switchPersona("auto").catch((error) => console.error("Failed to switch persona:", error));
+      }
       // Show/hide resolve error button
       const resolveButton = document.getElementById('resolveError');
       if (resolveButton) {
         resolveButton.style.display = mode === 'error' ? 'block' : 'none';
       }
     }
+    if (mode === 'error') {
+      await showNeedsHumanReason();
+    }
   } catch (error) {
     console.error('Failed to poll mode:', error);
   }
 }
 
+// Show why development halted next to the mode toggle
+async function showNeedsHumanReason() {
+  const reasonElement = document.getElementById('needsHumanReason');
+  if (!reasonElement) return;
+  try {
+    const response = await fetch('/needs-human-reason');
+    const reason = await response.json();
+    reasonElement.textContent = reason || '';
+    reasonElement.title = reason || '';
+  } catch (error) {
+    console.error('Failed to fetch reason:', error);
+  }
+}
+
 // Update mode toggle button
 function updateModeToggle() {
   const modeToggle = document.getElementById('modeToggle');
@@ -59,9 +79,13 @@ async function toggleMode() {
   }
 }
 let messages = [];
+// Tool calls and job results, shown apart from the conversation so they stay out of TRANSCRIPT.md
+let functionCalls = [];
 let textInput = "";
 let dataChannel = null;
 let pc = null;
+// A connection whose data channel hasn't opened yet, replaced by the next attempt
+let pendingPc = null;
 let isMuted = false;
 let audioTrack = null;
 let audioContext = null;
@@ -78,6 +102,8 @@ const sendButton = document.getElementById("sendButton");
 const messagesContainer = document.getElementById("messages");
 const functionCallsContainer = document.getElementById("functionCalls");
 let contexts = [];
+// The model only hears the end of long aider output
+const MAX_JOB_RESULT_LENGTH = 2000;
 
 async function fetchContexts() {
   try {
@@ -145,267 +171,860 @@ function updateUI() {
 
   // Scroll to bottom of messages
   messagesContainer.scrollTop = messagesContainer.scrollHeight;
+
+  // Update tool calls
+  functionCallsContainer.replaceChildren(
+    ...functionCalls.map((call) => {
+      const card = document.createElement("div");
+      const name = document.createElement("div");
+      name.className = "function-name";
+      name.textContent = call.name;
+      const args = document.createElement("div");
+      args.className = "function-args";
+      args.textContent = call.args;
+      card.append(name, args);
+      return card;
+    })
+  );
+  functionCallsContainer.scrollTop = functionCallsContainer.scrollHeight;
+}
+
+// Text chat through the server, used when there is no voice session
+async function sendChatMessage(text) {
+  messages.push({ type: "user", content: text });
+  textInputArea.value = "";
+  updateUI();
+
+  try {
+    const response = await fetch("/chat", {
+      method: "POST",
+      headers: {
+        "Content-Type": "application/json",
+      },
+      body: JSON.stringify({ message: text }),
+    });
+    const data = await response.json();
+    if (!response.ok) {
+      throw new Error(data.error || response.statusText);
+    }
+    messages.push({ type: "assistant", content: data });
+  } catch (error) {
+    console.error("Chat failed:", error);
+    // Errors go to the tools panel, the conversation is saved to TRANSCRIPT.md as requirements
+    functionCalls.push({ name: "chat failed", args: error.message });
+  }
+  updateUI();
 }
 
 async function handleSendMessage() {
   const text = textInputArea.value.trim();
-  if (!text || !dataChannel) return;
+  if (!text) return;
+  if (!dataChannel || dataChannel.readyState !== "open") {
+    await sendChatMessage(text);
+    return;
+  }
 
   console.log("Sending message to OpenAI:", text);
-  dataChannel.send(
-    JSON.stringify({
-      type: "conversation.item.create",
-      item: {
-        type: "message",
-        role: "user",
-        content: [
-          {
-            type: "input_text",
-            text: text,
-          },
-        ],
+  queueUserText(text);
+
+  messages.push({ type: "user", content: text });
+  textInputArea.value = "";
+  updateUI();
+}
+
+// The model of the open session, the settings may have changed since it started
+let sessionModel = null;
+
+async function reportRealtimeUsage(usage) {
+  try {
+    await fetch("/usage/realtime", {
+      method: "POST",
+      headers: {
+        "Content-Type": "application/json",
       },
-    })
+      body: JSON.stringify({ usage, model: sessionModel }),
+    });
+  } catch (error) {
+    console.error("Failed to report usage:", error);
+  }
+}
+
+// Everything the assistant responds to goes through one queue: typed messages, progress
+// commentary and function call results. Items wait while a response is in progress or
+// function calls are still running, then go out together with a single response.create,
+// so responses never overlap and nothing cuts the assistant off.
+let responseInProgress = false;
+// A response.create was sent and the server hasn't created or rejected the response yet
+let responseRequested = false;
+let functionCallsRunning = 0;
+let pendingItems = [];
+
+function queueConversationItem(item) {
+  if (!dataChannel || dataChannel.readyState !== "open") {
+    return;
+  }
+  pendingItems.push(item);
+  flushConversation();
+}
+
+function queueUserText(text) {
+  queueConversationItem({
+    type: "message",
+    role: "user",
+    content: [
+      {
+        type: "input_text",
+        text: text,
+      },
+    ],
+  });
+}
+
+function flushConversation() {
+  if (responseInProgress || responseRequested || functionCallsRunning > 0 || pendingItems.length === 0) {
+    return;
+  }
+  if (!dataChannel || dataChannel.readyState !== "open") {
+    return;
+  }
+  responseRequested = true;
+  pendingItems.splice(0).forEach((item) =>
+    dataChannel.send(JSON.stringify({ type: "conversation.item.create", item }))
   );
-  dataChannel.send(
-    JSON.stringify({
-      type: "response.create",
+  dataChannel.send(JSON.stringify({ type: "response.create" }));
+}
+
+// Jobs this page submitted, by id, so their results can be announced when they finish
+const pendingJobs = new Map();
+
+// Submits a code change or question as a server job; the conversation goes on and the
+// assistant speaks the result when the job's finished event arrives
+async function startCodeJob(endpoint, body, description) {
+  const response = await fetch(endpoint, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify(body),
+  });
+  const job = await response.json();
+  if (!response.ok) {
+    throw new Error(job.error || response.statusText);
+  }
+
+  pendingJobs.set(job.id, job);
+  functionCalls.push({ name: `job ${job.id} started`, args: description });
+  return {
+    job_id: job.id,
+    status: job.status,
+    note: "This takes a few minutes. Tell the user it has started; the result will follow in a later message.",
+  };
+}
+
+// Jobs that waited at least this long for the repository are announced when they start
+const JOB_WAIT_ANNOUNCE_MS = 5000;
+
+// The job as the assistant sees it, with the end of aider's output
+function describeJob(job) {
+  return {
+    job_id: job.id,
+    kind: job.kind,
+    request: job.request,
+    status: job.status,
+    output: job.output ? job.output.slice(-MAX_JOB_RESULT_LENGTH) : null,
+    error: job.error,
+  };
+}
+
+function handleJobUpdate(job) {
+  const pending = pendingJobs.get(job.id);
+  if (!pending) {
+    return;
+  }
+  if (job.status === "running" && pending.status === "queued") {
+    pendingJobs.set(job.id, job);
+    const waited = Date.parse(job.started_at) - Date.parse(job.submitted_at);
+    if (waited >= JOB_WAIT_ANNOUNCE_MS) {
+      functionCalls.push({ name: `job ${job.id} running`, args: job.request });
+      queueUserText(
+        `[Job ${job.id} (${job.kind}) is running now, after waiting ${Math.round(waited / 1000)} seconds for another agent to finish with the code] Tell me in one short sentence.`
+      );
+      updateUI();
+    }
+    return;
+  }
+  if (job.status !== "done" && job.status !== "failed") {
+    return;
+  }
+  pendingJobs.delete(job.id);
+  if (job.status === "done") {
+    functionCalls.push({ name: `job ${job.id} finished`, args: job.output });
+    queueUserText(
+      `[Job ${job.id} (${job.kind}) finished] Tell me briefly what happened. Aider's output:\n${job.output.slice(-MAX_JOB_RESULT_LENGTH)}`
+    );
+  } else {
+    functionCalls.push({ name: `job ${job.id} failed`, args: job.error });
+    queueUserText(`[Job ${job.id} (${job.kind}) failed] Tell me briefly: ${job.error}`);
+  }
+  updateUI();
+}
+
+// Catches up on jobs that finished while the event stream was disconnected
+async function pollPendingJobs() {
+  for (const id of pendingJobs.keys()) {
+    try {
+      const response = await fetch(`/jobs/${id}`);
+      if (response.ok) {
+        handleJobUpdate(await response.json());
+      }
+    } catch (error) {
+      console.error(`Failed to poll job ${id}:`, error);
+    }
+  }
+}
+
+// Shows who is working on the repository, e.g. "🔒 developer writing"
+function updateRepoLock(holders) {
+  const repoLock = document.getElementById("repoLock");
+  if (!repoLock) return;
+  repoLock.textContent = holders.length
+    ? `🔒 ${holders
+        .map((h) => `${h.who} ${h.access === "write" ? "writing" : "reading"}`)
+        .join(", ")}`
+    : "";
+}
+
+async function fetchRepoLock() {
+  try {
+    const response = await fetch("/status");
+    if (response.ok) {
+      updateRepoLock((await response.json()).repo_lock);
+    }
+  } catch (error) {
+    console.error("Failed to fetch status:", error);
+  }
+}
+
+// Open questions from QUESTIONS.md first, answered ones struck through below them
+function renderQuestions(questions) {
+  const list = document.getElementById("questions");
+  const sorted = [
+    ...questions.filter((q) => q.status === "open"),
+    ...questions.filter((q) => q.status === "answered"),
+  ];
+  list.replaceChildren(
+    ...sorted.map((question) => {
+      const item = document.createElement("li");
+      if (question.assumption) {
+        const label = document.createElement("span");
+        label.className = "assumption";
+        label.textContent = "Assumption: ";
+        item.append(label);
+      }
+      const text = document.createElement("span");
+      text.textContent = question.text;
+      if (question.status === "answered") {
+        text.className = "answered";
+        text.title = question.answer || "";
+      }
+      item.append(text);
+      return item;
     })
   );
+  if (sorted.length === 0) {
+    list.textContent = "No open questions yet.";
+  }
+}
 
-  messages.push({ type: "user", content: text });
-  textInputArea.value = "";
-  updateUI();
+async function fetchQuestions() {
+  try {
+    const response = await fetch("/questions");
+    renderQuestions(await response.json());
+  } catch (error) {
+    console.error("Failed to fetch questions:", error);
+  }
 }
 
-async function requestVoiceCommentary(message) {
+const serverEvents = new EventSource("/events");
+serverEvents.addEventListener("open", () => {
+  pollPendingJobs();
+  fetchRepoLock();
+  fetchQuestions();
+});
+serverEvents.addEventListener("message", (e) => {
+  const event = JSON.parse(e.data);
+  if (event.type === "job") {
+    handleJobUpdate(event.job);
+  } else if (event.type === "repo_lock") {
+    updateRepoLock(event.holders);
+  } else if (event.type === "commentary") {
+    queueUserText(
+      `[Progress update from the development team] ${event.message}. Tell me about it in one short sentence, then carry on.`
+    );
+  } else if (event.type === "project_context") {
+    updateProjectContext(event.context);
+  } else if (event.type === "questions") {
+    renderQuestions(event.questions);
+  }
+});
+
+// The parts of the session's instructions, so the persona and project context can be swapped
+let personaInstructions = null;
+let resumeContext = null;
+let projectContext = null;
+// "auto" takes on the persona of the current mode
+let sessionPersona = "auto";
+let personas = [];
+const personaSelect = document.getElementById("personaSelect");
+
+// Sends the current instructions, the assistant uses them from its next response
+function sendInstructions() {
+  if (personaInstructions === null || !dataChannel || dataChannel.readyState !== "open") {
+    return;
+  }
+  const instructions = [personaInstructions, resumeContext, projectContext]
+    .filter(Boolean)
+    .join("\n\n");
   dataChannel.send(
     JSON.stringify({
-      type: "conversation.item.create",
-      item: {
-        type: "message",
-        role: "user",
-        content: [
-          {
-            type: "input_text",
-            text: message,
-          },
-        ],
-      },
+      type: "session.update",
+      session: { instructions },
     })
   );
 }
 
-async function init() {
-  isConnecting = true;
-  updateUI();
-  
-  // Start mode polling
-  modePollingInterval = setInterval(pollCurrentMode, 5000); // Poll every 5 seconds
+function updateProjectContext(context) {
+  projectContext = context;
+  sendInstructions();
+}
 
+async function fetchPersonas() {
   try {
-    // Get session data from our server
-    const response = await fetch("/api/sessions", {
-      method: "POST",
-    });
-    const data = await response.json();
-    const EPHEMERAL_KEY = data.client_secret.value;
-
-    // Create a peer connection
-    pc = new RTCPeerConnection();
+    const response = await fetch("/personas");
+    personas = await response.json();
+    personaSelect.replaceChildren(new Option("Persona by mode", "auto"));
+    for (const persona of personas) {
+      personaSelect.add(new Option(persona.title, persona.name));
+    }
+    personaSelect.value = sessionPersona;
+  } catch (error) {
+    console.error("Failed to fetch personas:", error);
+  }
+}
 
-    // Set up to play remote audio from the model
-    const audioEl = document.createElement("audio");
-    audioEl.autoplay = true;
-    pc.ontrack = (e) => (audioEl.srcObject = e.streams[0]);
+// Has the session take on the persona `name`, or the one for the current mode with "auto"
+async function switchPersona(name) {
+  const response = await fetch(`/personas/${encodeURIComponent(name)}`);
+  const persona = await response.json();
+  if (!response.ok) {
+    throw new Error(persona.error);
+  }
+  sessionPersona = name === "auto" ? "auto" : persona.name;
+  personaSelect.value = sessionPersona;
+  personaInstructions = persona.instructions;
+  sendInstructions();
+  return persona;
+}
 
-    // Configure initial session and set up data channel
-    dataChannel = pc.createDataChannel("oai-events");
-    dataChannel.addEventListener("open", async () => {
-      isConnecting = false;
-      isConnected = true;
-      updateUI();
-      console.log("Connected to OpenAI Realtime API");
+// Realtime sessions end after 30 minutes, so a fresh one takes over a little before
+const SESSION_RENEW_MS = 28 * 60 * 1000;
+// Waits between attempts to reconnect a dropped session, the last one repeats
+const RECONNECT_DELAYS_MS = [1000, 2000, 5000, 10000, 30000];
+let localStream = null;
+let renewTimer = null;
+let reconnectAttempt = 0;
+let reconnecting = false;
 
-      // First fetch contexts before sending function config
-      await fetchContexts();
+async function configureSession(channel) {
+  // First fetch contexts and personas before sending function config
+  await fetchContexts();
+  await fetchPersonas();
 
-      // Create context enum from fetched contexts
-      const contextEnum = contexts.map((ctx) => ctx.filename);
+  // Create context enum from fetched contexts, "None" lets aider pick the files itself
+  const contextEnum = [
+    ...new Set([...contexts.map((ctx) => ctx.filename), "None"]),
+  ];
 
-      const functionConfig = {
-        type: "session.update",
-        session: {
-          input_audio_format: "pcm16",
-          output_audio_format: "pcm16",
-          input_audio_transcription: {
-            model: "whisper-1",
+  const functionConfig = {
+    type: "session.update",
+    session: {
+      input_audio_format: "pcm16",
+      output_audio_format: "pcm16",
+      input_audio_transcription: {
+        model: "whisper-1",
+      },
+      tools: [
+        {
+          type: "function",
+          name: "toggle_microphone",
+          description: "Toggle the microphone mute state",
+          parameters: {
+            type: "object",
+            properties: {
+              action: {
+                type: "string",
+                enum: ["mute", "unmute"],
+                description: "Whether to mute or unmute the microphone",
+              },
+            },
+            required: ["action"],
           },
-          tools: [
-            {
-              type: "function",
-              name: "toggle_microphone",
-              description: "Toggle the microphone mute state",
-              parameters: {
-                type: "object",
-                properties: {
-                  action: {
-                    type: "string",
-                    enum: ["mute", "unmute"],
-                    description: "Whether to mute or unmute the microphone",
-                  },
-                },
-                required: ["action"],
+        },
+        {
+          type: "function",
+          name: "web_search",
+          description: "Search the web for information",
+          parameters: {
+            type: "object",
+            properties: {
+              question: {
+                type: "string",
+                description: "The question to ask",
               },
             },
-            {
-              type: "function",
-              name: "web_search",
-              description: "Search the web for information",
-              parameters: {
-                type: "object",
-                properties: {
-                  question: {
-                    type: "string",
-                    description: "The question to ask",
-                  },
-                },
-                required: ["question"],
+            required: ["question"],
+          },
+        },
+        {
+          type: "function",
+          name: "change_code",
+          description:
+            "Have the coding agent make a change to the project's code. Runs in the background and returns a job id; the result arrives in a later message.",
+          parameters: {
+            type: "object",
+            properties: {
+              change: {
+                type: "string",
+                description: "The change to make, described precisely",
+              },
+              context: {
+                type: "string",
+                enum: contextEnum,
+                description: "The context file listing the parts of the code the change touches",
               },
             },
-          ],
-          tool_choice: "auto",
+            required: ["change", "context"],
+          },
         },
-      };
-      dataChannel.send(JSON.stringify(functionConfig));
-    });
+        {
+          type: "function",
+          name: "ask_about_code",
+          description:
+            "Ask the coding agent a question about the project's code without changing it. Runs in the background and returns a job id; the answer arrives in a later message.",
+          parameters: {
+            type: "object",
+            properties: {
+              question: {
+                type: "string",
+                description: "The question about the code",
+              },
+              context: {
+                type: "string",
+                enum: contextEnum,
+                description: "The context file listing the parts of the code the question is about",
+              },
+            },
+            required: ["question", "context"],
+          },
+        },
+        {
+          type: "function",
+          name: "check_job",
+          description:
+            "Check on a change_code or ask_about_code job, e.g. when the user asks how it is going. Returns whether it is queued, running, done or failed, and aider's output once it is done.",
+          parameters: {
+            type: "object",
+            properties: {
+              job_id: {
+                type: "integer",
+                description: "The job id returned when the job was started",
+              },
+            },
+            required: ["job_id"],
+          },
+        },
+        {
+          type: "function",
+          name: "switch_persona",
+          description:
+            "Take on a different persona, e.g. when the user asks to talk to the architect or the QA lead. \"auto\" goes back to the persona for the current mode.",
+          parameters: {
+            type: "object",
+            properties: {
+              persona: {
+                type: "string",
+                enum: [...personas.map((persona) => persona.name), "auto"],
+                description: `The persona to take on: ${personas
+                  .map((persona) => `${persona.name} is the ${persona.title}`)
+                  .join(", ")}`,
+              },
+            },
+            required: ["persona"],
+          },
+        },
+      ],
+      tool_choice: "auto",
+    },
+  };
+  channel.send(JSON.stringify(functionConfig));
+}
 
-    // Add local audio track for microphone input
-    const ms = await navigator.mediaDevices.getUserMedia({
-      audio: true,
-    });
-    audioTrack = ms.getTracks()[0];
-    pc.addTrack(audioTrack);
-
-    // Set up audio analysis
-    audioContext = new AudioContext();
-    const source = audioContext.createMediaStreamSource(ms);
-    analyser = audioContext.createAnalyser();
-    analyser.fftSize = 256;
-    source.connect(analyser);
-    dataArray = new Uint8Array(analyser.frequencyBinCount);
-
-    // Start volume meter animation
-    function drawVolumeMeter() {
-      if (!analyser) return;
-
-      analyser.getByteFrequencyData(dataArray);
-      const average = dataArray.reduce((a, b) => a + b) / dataArray.length;
-      const volume = average / 256; // Normalize to 0-1
-
-      volumeMeterCtx.clearRect(
-        0,
-        0,
-        volumeMeterCanvas.width,
-        volumeMeterCanvas.height
-      );
-      volumeMeterCtx.fillStyle = isMuted ? "#9ca3af" : "#3b82f6";
-      volumeMeterCtx.fillRect(
-        0,
-        0,
-        volumeMeterCanvas.width * volume,
-        volumeMeterCanvas.height
-      );
+async function setupMicrophone() {
+  if (localStream) return;
+  // Add local audio track for microphone input
+  const ms = await navigator.mediaDevices.getUserMedia({
+    audio: true,
+  });
+  localStream = ms;
+  audioTrack = ms.getTracks()[0];
 
-      requestAnimationFrame(drawVolumeMeter);
-    }
-    drawVolumeMeter();
+  // Set up audio analysis
+  audioContext = new AudioContext();
+  const source = audioContext.createMediaStreamSource(ms);
+  analyser = audioContext.createAnalyser();
+  analyser.fftSize = 256;
+  source.connect(analyser);
+  dataArray = new Uint8Array(analyser.frequencyBinCount);
 
-    updateUI();
+  // Start volume meter animation
+  function drawVolumeMeter() {
+    if (!analyser) return;
+
+    analyser.getByteFrequencyData(dataArray);
+    const average = dataArray.reduce((a, b) => a + b) / dataArray.length;
+    const volume = average / 256; // Normalize to 0-1
 
-    dataChannel.addEventListener("message", (e) => {
-      const event = JSON.parse(e.data);
+    volumeMeterCtx.clearRect(
+      0,
+      0,
+      volumeMeterCanvas.width,
+      volumeMeterCanvas.height
+    );
+    volumeMeterCtx.fillStyle = isMuted ? "#9ca3af" : "#3b82f6";
+    volumeMeterCtx.fillRect(
+      0,
+      0,
+      volumeMeterCanvas.width * volume,
+      volumeMeterCanvas.height
+    );
 
-      // Log all incoming messages
-      console.log("Received from OpenAI:", event);
+    requestAnimationFrame(drawVolumeMeter);
+  }
+  drawVolumeMeter();
+}
 
-      // Handle function calls
-      if (event.type === "response.function_call_arguments.delta") {
-        console.log("Partial function call:", event.delta);
-      } else if (
-        event.type === "response.done" &&
-        event.response.output?.[0]?.type === "function_call"
-      ) {
-        const call = event.response.output[0];
+function handleRealtimeEvent(e) {
+  const event = JSON.parse(e.data);
+
+  // Log all incoming messages
+  console.log("Received from OpenAI:", event);
+
+  // Report token usage so the server can account for the session cost
+  if (event.type === "response.done" && event.response?.usage) {
+    reportRealtimeUsage(event.response.usage);
+  }
+
+  if (event.type === "response.created") {
+    responseRequested = false;
+    responseInProgress = true;
+  } else if (event.type === "response.done") {
+    responseInProgress = false;
+  } else if (event.type === "error") {
+    // A rejected request must not hold back everything queued behind it
+    console.error("Realtime error:", event.error);
+    functionCalls.push({ name: "realtime error", args: event.error?.message || "unknown error" });
+    responseRequested = false;
+    responseInProgress = false;
+    updateUI();
+    flushConversation();
+  }
+
+  // Handle function calls
+  if (event.type === "response.function_call_arguments.delta") {
+    console.log("Partial function call:", event.delta);
+  } else if (event.type === "response.done") {
+    const calls = (event.response.output || []).filter(
+      (item) => item.type === "function_call"
+    );
+    if (calls.length > 0) {
+      calls.forEach((call) =>
         functionCalls.push({
           name: call.name,
           args: call.arguments,
-        });
-        handleFunctionCall(call);
-        updateUI();
-      }
+        })
+      );
+      handleFunctionCalls(calls);
+      updateUI();
+    }
+    // After the calls are counted as running, so their results go out with anything queued
+    flushConversation();
+  }
 
-      // Handle audio transcript events
-      if (event.type === "response.output_item.done") {
-        // Add completed output item transcript to messages
-        if (
-          event.item?.content?.[0]?.type === "audio" &&
-          event.item.content[0].transcript
-        ) {
-          messages.push({
-            type: "assistant",
-            content: event.item.content[0].transcript,
-          });
-          updateUI();
-        }
-      } else if (
-        event.type === "conversation.item.input_audio_transcription.completed"
-      ) {
-        if (event.transcript) {
-          messages.push({
-            type: "user",
-            content: event.transcript,
-          });
-          updateUI();
-        }
-      }
-    });
+  // Handle audio transcript events
+  if (event.type === "response.output_item.done") {
+    // Add completed output item transcript to messages
+    if (
+      event.item?.content?.[0]?.type === "audio" &&
+      event.item.content[0].transcript
+    ) {
+      messages.push({
+        type: "assistant",
+        content: event.item.content[0].transcript,
+      });
+      updateUI();
+    }
+  } else if (
+    event.type === "conversation.item.input_audio_transcription.completed"
+  ) {
+    if (event.transcript) {
+      messages.push({
+        type: "user",
+        content: event.transcript,
+      });
+      updateUI();
+    }
+  }
+}
 
-    // Start the session using the Session Description Protocol (SDP)
-    const offer = await pc.createOffer();
-    await pc.setLocalDescription(offer);
+// Opens a Realtime session and swaps it in for the current one once its data channel is
+// open. `resume` has the server seed it with a summary of the conversation so far.
+async function connect(resume) {
+  // Get session data from our server
+  const response = await fetch("/api/sessions", {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ resume, persona: sessionPersona }),
+  });
+  const data = await response.json();
+  if (!response.ok) {
+    throw new Error(data.error || response.statusText);
+  }
+  const EPHEMERAL_KEY = data.client_secret.value;
 
-    const baseUrl = "https://api.openai.com/v1/realtime";
-    const model = "gpt-4o-realtime-preview-2024-12-17";
-    const sdpResponse = await fetch(`${baseUrl}?model=${model}`, {
-      method: "POST",
-      body: offer.sdp,
-      headers: {
-        Authorization: `Bearer ${EPHEMERAL_KEY}`,
-        "Content-Type": "application/sdp",
-      },
-    });
+  // Create a peer connection, giving up on any earlier attempt still being set up
+  if (pendingPc) pendingPc.close();
+  const newPc = new RTCPeerConnection();
+  pendingPc = newPc;
+
+  // Set up to play remote audio from the model
+  const audioEl = document.createElement("audio");
+  audioEl.autoplay = true;
+  newPc.ontrack = (e) => (audioEl.srcObject = e.streams[0]);
+
+  const newChannel = newPc.createDataChannel("oai-events");
+  newChannel.addEventListener("open", async () => {
+    const oldPc = pc;
+    pc = newPc;
+    dataChannel = newChannel;
+    if (pendingPc === newPc) pendingPc = null;
+    sessionModel = data.model;
+    personaInstructions = data.persona.instructions;
+    resumeContext = data.resume_context;
+    projectContext = data.project_context;
+    if (oldPc) oldPc.close();
 
-    const answer = {
-      type: "answer",
-      sdp: await sdpResponse.text(),
-    };
-    await pc.setRemoteDescription(answer);
-  } catch (error) {
-    console.error("Failed to initialize:", error);
-  } finally {
     isConnecting = false;
+    isConnected = true;
+    responseInProgress = false;
+    responseRequested = false;
+    // Results of the old session's function calls mean nothing to the new one
+    pendingItems = pendingItems.filter((item) => item.type === "message");
+    reconnectAttempt = 0;
     updateUI();
+    console.log("Connected to OpenAI Realtime API");
+
+    clearTimeout(renewTimer);
+    renewTimer = setTimeout(renewSession, SESSION_RENEW_MS);
+    await configureSession(newChannel);
+  });
+  newChannel.addEventListener("message", handleRealtimeEvent);
+  newChannel.addEventListener("close", () => {
+    if (newChannel === dataChannel) handleDrop();
+  });
+  newPc.addEventListener("connectionstatechange", () => {
+    if (newPc === pc && newPc.connectionState === "failed") handleDrop();
+  });
+
+  // Add local audio track for microphone input
+  newPc.addTrack(audioTrack, localStream);
+
+  // Start the session using the Session Description Protocol (SDP)
+  const offer = await newPc.createOffer();
+  await newPc.setLocalDescription(offer);
+
+  // The server says which provider and model the session was created with
+  const baseUrl = data.realtime_url;
+  const model = data.model;
+  const sdpResponse = await fetch(`${baseUrl}?model=${model}`, {
+    method: "POST",
+    body: offer.sdp,
+    headers: {
+      Authorization: `Bearer ${EPHEMERAL_KEY}`,
+      "Content-Type": "application/sdp",
+    },
+  });
+  if (!sdpResponse.ok) {
+    newPc.close();
+    if (pendingPc === newPc) pendingPc = null;
+    throw new Error(`Realtime connection failed: ${sdpResponse.status}`);
   }
 
-  // Clear polling interval if initialization fails
-  if (!isConnected) {
+  const answer = {
+    type: "answer",
+    sdp: await sdpResponse.text(),
+  };
+  await newPc.setRemoteDescription(answer);
+}
+
+// Replaces the session before it expires, waiting for the assistant to finish speaking
+async function renewSession() {
+  if (responseInProgress) {
+    renewTimer = setTimeout(renewSession, 2000);
+    return;
+  }
+  console.log("Renewing Realtime session");
+  try {
+    await connect(true);
+  } catch (error) {
+    // The current session still works until it expires, so try again shortly
+    console.error("Failed to renew session:", error);
+    renewTimer = setTimeout(renewSession, RECONNECT_DELAYS_MS[RECONNECT_DELAYS_MS.length - 1]);
+  }
+}
+
+// Closes the current session and any still being set up, without treating it as a drop
+function closeSessions() {
+  const channel = dataChannel;
+  const oldPc = pc;
+  const pending = pendingPc;
+  dataChannel = null;
+  pc = null;
+  pendingPc = null;
+  if (channel) channel.close();
+  if (oldPc) oldPc.close();
+  if (pending) pending.close();
+}
+
+// Reconnects after the session dropped, backing off between attempts
+async function handleDrop() {
+  if (reconnecting || isConnecting) return;
+  reconnecting = true;
+  isConnected = false;
+  clearTimeout(renewTimer);
+  // Only one session at a time: the dropped one and a half-open renewal go first
+  closeSessions();
+  updateUI();
+
+  while (!isConnected) {
+    const delay = RECONNECT_DELAYS_MS[Math.min(reconnectAttempt, RECONNECT_DELAYS_MS.length - 1)];
+    reconnectAttempt++;
+    console.log(`Session dropped, reconnecting in ${delay}ms`);
+    await new Promise((resolve) => setTimeout(resolve, delay));
+    isConnecting = true;
+    updateUI();
+    try {
+      await connect(true);
+      // Connected once the new data channel opens, otherwise the next attempt replaces it
+      await new Promise((resolve) => setTimeout(resolve, 5000));
+    } catch (error) {
+      console.error("Reconnect failed:", error);
+    }
+  }
+  reconnecting = false;
+}
+
+async function init() {
+  if (isConnected || isConnecting) return;
+  isConnecting = true;
+  updateUI();
+
+  // Start mode polling
+  modePollingInterval = setInterval(pollCurrentMode, 5000); // Poll every 5 seconds
+
+  try {
+    await setupMicrophone();
+    updateUI();
+    await connect(false);
+  } catch (error) {
+    console.error("Failed to initialize:", error);
+    isConnecting = false;
     clearInterval(modePollingInterval);
+    updateUI();
   }
 }
 
 // Event Listeners
 connectButton.addEventListener("click", init);
+personaSelect.addEventListener("change", async () => {
+  if (!isConnected) {
+    sessionPersona = personaSelect.value;
+    return;
+  }
+  try {
+    await switchPersona(personaSelect.value);
+  } catch (error) {
+    console.error("Failed to switch persona:", error);
+  }
+});
+fetchPersonas();
+
+// Voice, model and language apply from the next session, the open one keeps its own
+const voiceSelect = document.getElementById("voiceSelect");
+const modelSelect = document.getElementById("modelSelect");
+const modelInput = document.getElementById("modelInput");
+const languageInput = document.getElementById("languageInput");
+const voiceSettingsStatus = document.getElementById("voiceSettingsStatus");
+
+function showVoiceSettings(settings) {
+  voiceSelect.replaceChildren(...settings.voices.map((voice) => new Option(voice, voice)));
+  voiceSelect.value = settings.voice;
+  // Providers other than OpenAI name their models freely
+  const knownModels = settings.models.length > 0;
+  modelSelect.style.display = knownModels ? "" : "none";
+  modelInput.style.display = knownModels ? "none" : "";
+  modelSelect.replaceChildren(...settings.models.map((model) => new Option(model, model)));
+  modelSelect.value = settings.model;
+  modelInput.value = settings.model;
+  languageInput.value = settings.language;
+}
+
+async function fetchVoiceSettings() {
+  try {
+    const response = await fetch("/voice-settings");
+    showVoiceSettings(await response.json());
+  } catch (error) {
+    console.error("Failed to fetch voice settings:", error);
+  }
+}
+
+document.getElementById("saveVoiceSettings").addEventListener("click", async () => {
+  try {
+    const response = await fetch("/voice-settings", {
+      method: "POST",
+      headers: {
+        "Content-Type": "application/json",
+      },
+      body: JSON.stringify({
+        voice: voiceSelect.value,
+        model: modelSelect.style.display === "none" ? modelInput.value : modelSelect.value,
+        language: languageInput.value,
+      }),
+    });
+    const result = await response.json();
+    if (!response.ok) {
+      throw new Error(result.error);
+    }
+    showVoiceSettings(result);
+    voiceSettingsStatus.style.color = "#6b7280";
+    voiceSettingsStatus.textContent = isConnected ? "Saved, used from the next session" : "Saved";
+  } catch (error) {
+    voiceSettingsStatus.style.color = "#ef4444";
+    voiceSettingsStatus.textContent = error.message;
+  }
+});
+fetchVoiceSettings();
 document.getElementById('modeToggle')?.addEventListener('click', toggleMode);
 
 // Add resolve error button handler
@@ -423,6 +1042,7 @@ document.getElementById('resolveError')?.addEventListener('click', async () => {
       currentMode = 'planning';
       updateModeToggle();
       document.getElementById('resolveError').style.display = 'none';
+      document.getElementById('needsHumanReason').textContent = '';
     }
   } catch (error) {
     console.error('Failed to resolve error:', error);
@@ -446,62 +1066,121 @@ textInputArea.addEventListener("keydown", (e) => {
   }
 });
 
-async function handleFunctionCall(call) {
-  try {
-    const args = JSON.parse(call.arguments);
-    let response;
-
-    switch (call.name) {
-      case "toggle_microphone":
-        if (audioTrack) {
-          if (args.action === "mute" && !isMuted) {
-            isMuted = true;
-            audioTrack.enabled = false;
-          } else if (args.action === "unmute" && isMuted) {
-            isMuted = false;
-            audioTrack.enabled = true;
-          }
-          messages.push({
-            type: "assistant",
-            content: `Microphone is now ${isMuted ? "muted" : "unmuted"}`,
-          });
-          updateUI();
-          return;
-        }
-        throw new Error("No microphone available");
-
-      case "web_search":
-        const searchResponse = await fetch("/web-search", {
-          method: "POST",
-          headers: {
-            "Content-Type": "application/json",
-          },
-          body: JSON.stringify({
-            question: args.question,
-          }),
-        });
-        const searchResult = await searchResponse.json();
-
-        messages.push({
-          type: "assistant",
-          content: `Search: ${args.question} Result: ${searchResult}`,
-        });
-
-        break;
-
-      default:
-        console.warn("Unknown function call:", call.name);
-        return;
+// Each tool takes the parsed arguments and returns the result the model is told about;
+// throwing reports the error to the model instead
+const toolHandlers = {
+  toggle_microphone: async (args) => {
+    if (!audioTrack) {
+      throw new Error("No microphone available");
     }
+    if (args.action === "mute" && !isMuted) {
+      isMuted = true;
+      audioTrack.enabled = false;
+    } else if (args.action === "unmute" && isMuted) {
+      isMuted = false;
+      audioTrack.enabled = true;
+    }
+    messages.push({
+      type: "assistant",
+      content: `Microphone is now ${isMuted ? "muted" : "unmuted"}`,
+    });
+    return { muted: isMuted };
+  },
 
-    // Update UI with any results from the function calls
-    updateUI();
+  switch_persona: async (args) => {
+    const persona = await switchPersona(args.persona);
+    return { persona: persona.title };
+  },
+
+  web_search: async (args) => {
+    const searchResponse = await fetch("/web-search", {
+      method: "POST",
+      headers: {
+        "Content-Type": "application/json",
+      },
+      body: JSON.stringify({
+        question: args.question,
+      }),
+    });
+    const searchResult = await searchResponse.json();
+    if (!searchResponse.ok) {
+      throw new Error(searchResult.error);
+    }
+    const sources = searchResult.citations
+      .map((citation, i) => `[${i + 1}] ${citation.url}`)
+      .join("\n");
+
+    messages.push({
+      type: "assistant",
+      content: `Search: ${args.question} Result: ${searchResult.answer}${sources ? `\nSources:\n${sources}` : ""}`,
+    });
+    return searchResult;
+  },
+
+  change_code: async (args) =>
+    startCodeJob(
+      "/change-code",
+      { change: args.change, context: args.context || "None" },
+      args.change
+    ),
+
+  ask_about_code: async (args) =>
+    startCodeJob(
+      "/ask-question",
+      { question: args.question, context: args.context || "None" },
+      args.question
+    ),
+
+  check_job: async (args) => {
+    const response = await fetch(`/jobs/${args.job_id}`);
+    const job = await response.json();
+    if (!response.ok) {
+      throw new Error(job.error || response.statusText);
+    }
+    return describeJob(job);
+  },
+};
+
+// Runs one tool call, always producing an output so the model never waits on a call
+async function runFunctionCall(call) {
+  try {
+    const handler = toolHandlers[call.name];
+    if (!handler) {
+      throw new Error(`Unknown function: ${call.name}`);
+    }
+    const args = call.arguments ? JSON.parse(call.arguments) : {};
+    return { result: await handler(args) };
   } catch (error) {
     console.error("Error handling function call:", error);
-    messages.push({ type: "assistant", content: `Error: ${error.message}` });
-    updateUI();
+    functionCalls.push({ name: `${call.name} failed`, args: error.message });
+    return { error: error.message };
   }
 }
 
+function queueFunctionOutput(callId, output) {
+  queueConversationItem({
+    type: "function_call_output",
+    call_id: callId,
+    output: JSON.stringify(output),
+  });
+}
+
+// Answers every call of a response, then lets the model continue with the results
+async function handleFunctionCalls(calls) {
+  const channel = dataChannel;
+  functionCallsRunning++;
+  try {
+    const outputs = await Promise.all(calls.map(runFunctionCall));
+    // A session that replaced the one that made the calls doesn't know them
+    if (dataChannel === channel) {
+      calls.forEach((call, i) => queueFunctionOutput(call.call_id, outputs[i]));
+    }
+  } finally {
+    functionCallsRunning--;
+  }
+  flushConversation();
+  updateUI();
+}
+
 // Initial UI update
 updateUI();
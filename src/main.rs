@@ -1,5 +1,5 @@
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     response::{Html, Json},
     routing::{get, post},
@@ -7,19 +7,22 @@ use axum::{
 };
 mod cli;
 mod init;
-use cli::{Cli, Commands};
+mod doctor;
+mod headless;
+mod templates;
+use cli::{Cli, Commands, DoctorArgs};
 use clap::Parser;
 use colored::*;
 use dotenv::dotenv;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::BTreeMap;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::net::SocketAddr;
-use std::process::Command;
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 
 #[derive(Serialize)]
 struct ErrorResponse {
@@ -28,92 +31,48 @@ struct ErrorResponse {
 }
 
 async fn handle_web_search(
+    State(state): State<Arc<AppStateWithDir>>,
     Json(payload): Json<WebSearchRequest>,
-) -> Result<Json<String>, (StatusCode, Json<ErrorResponse>)> {
-    let api_key = std::env::var("PERPLEXITY_API_KEY").map_err(|_| {
-        (
-            StatusCode::OK,
-            Json(ErrorResponse {
-                error: "I can't search the web right now because the Perplexity API key is not configured.".to_string(),
-                project_dir: String::new(),
-            }),
-        )
-    })?;
-
-    let client = reqwest::Client::new();
-    let search_request = json!({
-        "model": "llama-3.1-sonar-small-128k-online",
-        "messages": [
-            {
-                "role": "system",
-                "content": "Be precise and concise."
-            },
-            {
-                "role": "user",
-                "content": payload.question
-            }
-        ],
-        "temperature": 0.2,
-        "top_p": 0.9,
-        "search_domain_filter": ["perplexity.ai"],
-        "return_images": false,
-        "return_related_questions": false,
-        "search_recency_filter": "month",
-        "top_k": 0,
-        "stream": false,
-        "presence_penalty": 0,
-        "frequency_penalty": 1
-    });
-
-    let response = client
-        .post("https://api.perplexity.ai/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&search_request)
-        .send()
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to search web: {}", e),
-                    project_dir: String::new(),
-                }),
-            )
-        })?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_body = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Could not read error response".to_string());
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: format!("API error: {} - {}", status, error_body),
-                project_dir: String::new(),
-            }),
-        ));
+) -> Result<Json<SearchResult>, (StatusCode, Json<ErrorResponse>)> {
+    if state.usage.is_paused("web_search").await {
+        return Err(budget_exceeded("web_search"));
     }
 
-    let json: serde_json::Value = response.json().await.map_err(|e| {
+    let result = search::search(
+        &state.providers.search,
+        &state.providers.search_options,
+        std::path::Path::new(&state.project_dir),
+        &payload.question,
+    )
+    .await
+    .map_err(|e| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: format!("Failed to parse response: {}", e),
+                error: format!("I couldn't search for that: {}", e),
                 project_dir: String::new(),
             }),
         )
     })?;
 
-    // Extract the content from the response
-    let content = json["choices"][0]["message"]["content"]
-        .as_str()
-        .unwrap_or("No content found in response")
-        .to_string();
+    if result.source == "perplexity" {
+        state
+            .usage
+            .record_perplexity(&state.providers.search.model, &result.usage)
+            .await;
+    }
+
+    Ok(Json(result))
+}
 
-    Ok(Json(content))
+fn budget_exceeded(role: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::PAYMENT_REQUIRED,
+        Json(ErrorResponse {
+            error: format!("The budget for {} has been used up.", role),
+            project_dir: String::new(),
+        }),
+    )
 }
 
 #[derive(Serialize)]
@@ -149,6 +108,18 @@ struct TranscriptUpdate {
     content: String,
 }
 
+#[derive(Deserialize)]
+struct ChatRequest {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RealtimeUsage {
+    usage: serde_json::Value,
+    /// The session's model, which may no longer be the one in the settings
+    model: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct SessionRequest {
     model: String,
@@ -156,6 +127,15 @@ struct SessionRequest {
     instructions: String,
 }
 
+#[derive(Deserialize, Default)]
+struct SessionOptions {
+    /// Renewing or reconnecting an earlier session rather than starting a new one
+    #[serde(default)]
+    resume: bool,
+    /// Persona to start with, `auto` or none follows the mode
+    persona: Option<String>,
+}
+
 #[derive(Clone)]
 pub enum ActivityMode {
     Planning,
@@ -166,12 +146,95 @@ pub enum ActivityMode {
 struct AppStateWithDir {
     shutdown_signal: Arc<Mutex<bool>>,
     project_dir: String,
-    model: String,
-    preferred_language: String,
-    instructions: String,
-    voice: String,
+    providers: Providers,
+    /// Voice, model and language of the next voice session
+    voice_settings: Mutex<VoiceSettings>,
+    /// Replaces the interviewer persona's instructions
+    instructions: Option<String>,
+    /// Overrides the persona chosen by mode
+    persona: Option<String>,
     code_model: Option<String>,
+    escalation_model: Option<String>,
     activity_mode: Arc<Mutex<ActivityMode>>,
+    review: bool,
+    tdd: bool,
+    needs_human_reason: Mutex<Option<String>>,
+    usage: UsageTracker,
+    role_states: Mutex<BTreeMap<String, String>>,
+    chat_history: Mutex<Vec<ChatMessage>>,
+    repo_lock: RepoLock,
+    jobs: Jobs,
+    events: broadcast::Sender<Event>,
+    commentary: Verbosity,
+    briefing: ProjectBriefing,
+}
+
+/// The providers for `project_dir`, exiting when the providers file can't be used.
+fn load_providers(path: Option<&str>, project_dir: &str) -> Providers {
+    Providers::load(path, project_dir).unwrap_or_else(|e| {
+        eprintln!("{}", e.bright_red());
+        std::process::exit(1);
+    })
+}
+
+/// The persona `name`, or the one for `--persona` or the current mode, with the preferred
+/// language in its instructions; shared by voice sessions and text chat.
+async fn session_persona(state: &AppStateWithDir, name: Option<&str>) -> Result<Persona, String> {
+    let available = personas::available(&state.project_dir, state.instructions.as_deref());
+    let name = match name.filter(|name| *name != "auto").or(state.persona.as_deref()) {
+        Some(name) => name.to_string(),
+        None => personas::for_mode(&*state.activity_mode.lock().await).to_string(),
+    };
+    let mut persona = personas::find(&available, &name)?;
+    persona.instructions = format!(
+        "The preferred language is {}. {}",
+        state.voice_settings.lock().await.language,
+        persona.instructions
+    );
+    Ok(persona)
+}
+
+/// Model name to attribute aider usage to when no code model was configured.
+fn code_model_name(state: &AppStateWithDir) -> &str {
+    state.code_model.as_deref().unwrap_or("aider-default")
+}
+
+impl AppStateWithDir {
+    /// Halts development and records why, so the UI can tell the human what to fix.
+    async fn needs_human(&self, reason: &str) {
+        println!("SOMETHING IS SERIOUSLY WRONG - {}", reason);
+        self.comment(Verbosity::Important, &format!("Development stopped and needs you: {}", reason));
+        let mut mode = self.activity_mode.lock().await;
+        let mut needs_human_reason = self.needs_human_reason.lock().await;
+        *needs_human_reason = match (&*mode, needs_human_reason.take()) {
+            // Keep the original cause when several agents give up on the same problem
+            (ActivityMode::ErrorNeedsHuman, Some(existing)) => Some(format!("{}\n{}", existing, reason)),
+            _ => Some(reason.to_string()),
+        };
+        *mode = ActivityMode::ErrorNeedsHuman;
+    }
+
+    /// Records what a role is doing, for `/status`.
+    async fn set_role_state(&self, role: &str, state: &str) {
+        self.role_states
+            .lock()
+            .await
+            .insert(role.to_string(), state.to_string());
+    }
+
+    /// Publishes an event to every connected browser; nobody listening is fine.
+    fn emit(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+
+    /// Has the voice assistant mention `message`, unless `--commentary` is below `level`.
+    fn comment(&self, level: Verbosity, message: &str) {
+        if self.commentary != Verbosity::Off && level <= self.commentary {
+            self.emit(Event::Commentary {
+                message: message.to_string(),
+            });
+        }
+    }
 }
 
 async fn get_contexts(
@@ -191,26 +254,24 @@ async fn get_contexts(
     })?;
 
     // Filter and process context files
-    for entry in entries {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                if filename.starts_with("CONTEXT_") && filename.ends_with(".md") {
-                    let content = fs::read_to_string(&path).map_err(|e| {
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(ErrorResponse {
-                                error: format!("Failed to read file {}: {}", filename, e),
-                                project_dir: static_dir.clone(),
-                            }),
-                        )
-                    })?;
-
-                    contexts.push(Context {
-                        filename: filename.to_string(),
-                        content,
-                    });
-                }
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
+            if filename.starts_with("CONTEXT_") && filename.ends_with(".md") {
+                let content = fs::read_to_string(&path).map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ErrorResponse {
+                            error: format!("Failed to read file {}: {}", filename, e),
+                            project_dir: static_dir.clone(),
+                        }),
+                    )
+                })?;
+
+                contexts.push(Context {
+                    filename: filename.to_string(),
+                    content,
+                });
             }
         }
     }
@@ -228,30 +289,55 @@ async fn get_contexts(
 
 async fn create_session(
     State(state): State<Arc<AppStateWithDir>>,
+    options: Option<Json<SessionOptions>>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    if state.usage.is_paused("interviewer").await {
+        return Err(budget_exceeded("interviewer"));
+    }
+
+    let realtime = &state.providers.realtime;
+    let settings = state.voice_settings.lock().await.clone();
+    let options = options.map(|Json(options)| options).unwrap_or_default();
+    let persona = session_persona(&state, options.persona.as_deref())
+        .await
+        .map_err(|error| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error,
+                    project_dir: String::new(),
+                }),
+            )
+        })?;
+    let resume_context = match options.resume {
+        true => Some(session::resume_context(&state).await),
+        false => None,
+    };
+    let project_context = session::project_context(&state).await;
+    let instructions = [Some(&persona.instructions), resume_context.as_ref(), Some(&project_context)]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("\n\n");
     let payload = SessionRequest {
-        model: state.model.clone(),
-        voice: state.voice.clone(),
-        instructions: format!(
-            "The preferred language is {}. {}",
-            state.preferred_language, state.instructions
-        ),
+        model: settings.model.clone(),
+        voice: settings.voice.clone(),
+        instructions,
     };
-    let api_key = std::env::var("OPENAI_API_KEY").map_err(|_| {
-        (
+    if realtime.api_key().is_none() {
+        return Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
-                error: "API key not found".to_string(),
+                error: format!("{} not found", realtime.api_key_env),
                 project_dir: String::new(),
             }),
-        )
-    })?;
+        ));
+    }
 
     let client = reqwest::Client::new();
-    let response = client
-        .post("https://api.openai.com/v1/realtime/sessions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
+    let response = realtime
+        .request(&client, reqwest::Method::POST, "realtime/sessions")
         .json(&payload)
         .send()
         .await
@@ -283,7 +369,7 @@ async fn create_session(
         ));
     }
 
-    let json = response.json().await.map_err(|e| {
+    let mut json: serde_json::Value = response.json().await.map_err(|e| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
@@ -292,26 +378,93 @@ async fn create_session(
             }),
         )
     })?;
+    // The browser sends its SDP offer straight to the provider, so it needs to know where
+    json["realtime_url"] = json!(realtime.url("realtime"));
+    json["model"] = json!(settings.model);
+    // The parts of the instructions, so the browser can swap the persona or project context
+    json["persona"] = json!(persona);
+    json["resume_context"] = json!(resume_context);
+    json["project_context"] = json!(project_context);
+
+    state
+        .usage
+        .record(UsageEntry {
+            provider: "openai-realtime".to_string(),
+            model: settings.model.clone(),
+            role: "interviewer".to_string(),
+            requests: 1,
+            ..Default::default()
+        })
+        .await;
 
     Ok(Json(json))
 }
 
+#[derive(Serialize)]
+struct VoiceSettingsReport {
+    #[serde(flatten)]
+    settings: VoiceSettings,
+    voices: &'static [&'static str],
+    /// Known models, empty when the Realtime provider isn't OpenAI and any name goes
+    models: &'static [&'static str],
+}
 
-fn check_requirements(project_dir: &str) -> Result<(), String> {
-    // Check for .git directory
-    let git_dir = std::path::Path::new(project_dir).join(".git");
-    if !git_dir.exists() {
-        return Err("No .git directory found. Please run this from a git repository.".to_string());
+async fn voice_settings_report(state: &AppStateWithDir) -> VoiceSettingsReport {
+    VoiceSettingsReport {
+        settings: state.voice_settings.lock().await.clone(),
+        voices: &voice::VOICES,
+        models: voice::known_models(&state.providers.realtime),
     }
+}
 
-    // Check for OPENAI_API_KEY
-    if std::env::var("OPENAI_API_KEY").is_err() {
-        return Err("OPENAI_API_KEY environment variable is not set. Put it in your environment variables or a .env file.".to_string());
+async fn get_voice_settings(State(state): State<Arc<AppStateWithDir>>) -> Json<VoiceSettingsReport> {
+    Json(voice_settings_report(&state).await)
+}
+
+/// `POST /voice-settings`: changes the voice, model or language of the next voice session.
+async fn handle_voice_settings(
+    State(state): State<Arc<AppStateWithDir>>,
+    Json(update): Json<VoiceSettingsUpdate>,
+) -> Result<Json<VoiceSettingsReport>, (StatusCode, Json<ErrorResponse>)> {
+    {
+        let mut settings = state.voice_settings.lock().await;
+        let updated = settings
+            .updated(update, &state.providers.realtime)
+            .map_err(|error| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error,
+                        project_dir: state.project_dir.clone(),
+                    }),
+                )
+            })?;
+        println!(
+            "🎙️ Next voice sessions use {} with the {} voice in {}",
+            updated.model, updated.voice, updated.language
+        );
+        *settings = updated;
     }
+    Ok(Json(voice_settings_report(&state).await))
+}
 
-    Ok(())
+async fn get_usage(State(state): State<Arc<AppStateWithDir>>) -> Json<UsageReport> {
+    Json(state.usage.report().await)
+}
+
+async fn handle_realtime_usage(
+    State(state): State<Arc<AppStateWithDir>>,
+    Json(payload): Json<RealtimeUsage>,
+) -> Json<String> {
+    let model = match payload.model {
+        Some(model) => model,
+        None => state.voice_settings.lock().await.model.clone(),
+    };
+    state.usage.record_realtime(&model, &payload.usage).await;
+    Json("Usage recorded".to_string())
 }
 
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
@@ -320,32 +473,107 @@ async fn main() {
     
     match cli.command {
         Commands::Init(args) => {
-            if let Err(e) = init::project_init(&args.dir) {
+            if let Err(e) = init::project_init(&args) {
                 eprintln!("Initialization failed: {}", e);
                 std::process::exit(1);
             }
         }
+        Commands::Doctor(args) => {
+            let mut providers = load_providers(args.providers.as_deref(), &args.project_dir);
+            if let Some(model) = &args.model {
+                providers.realtime.model = model.clone();
+            }
+            let report = doctor::run(&args, &providers).await;
+            report.print();
+            if report.has_failures() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Status(args) => status::status(&args).await,
+        Commands::Tasks(args) => status::tasks(&args).await,
+        Commands::Mode(args) => {
+            if let Err(e) = status::set_mode(&args).await {
+                eprintln!("{}", e.bright_red());
+                std::process::exit(1);
+            }
+        }
+        Commands::Run(args) => {
+            std::process::exit(headless::run(&args).await);
+        }
         Commands::Serve(args) => {
             // Start server with args
 
+    // Flags win over the providers file, which wins over the defaults
+    let mut providers = load_providers(args.providers.as_deref(), &args.project_dir);
+    if let Some(model) = &args.model {
+        providers.realtime.model = model.clone();
+    }
+    if let Some(base_url) = &args.chat_base_url {
+        providers.chat.base_url = base_url.clone();
+    }
+    if let Some(model) = &args.chat_model {
+        providers.chat.model = model.clone();
+    }
+
+    if let Some(persona) = &args.persona {
+        let available = personas::available(&args.project_dir, args.instructions.as_deref());
+        if let Err(e) = personas::find(&available, persona) {
+            eprintln!("{}", e.bright_red());
+            std::process::exit(1);
+        }
+    }
+
     // Check requirements before starting
-    if let Err(error) = check_requirements(&args.project_dir) {
-        eprintln!("{}", error.bright_red());
+    let report = doctor::run(
+        &DoctorArgs {
+            project_dir: args.project_dir.clone(),
+            port: args.port,
+            model: args.model.clone(),
+            code_model: args.code_model.clone(),
+            providers: args.providers.clone(),
+            offline: false,
+            headless: false,
+        },
+        &providers,
+    )
+    .await;
+    report.print();
+    if report.has_failures() {
+        eprintln!("{}", "Fix the failed checks above before serving, `colossus doctor` re-runs them.".bright_red());
         std::process::exit(1);
     }
 
+    git::exclude_run_state(std::path::Path::new(&args.project_dir)).await;
+
     // Initialize global state
     let shutdown_signal = Arc::new(Mutex::new(false));
 
+    let events = events::channel();
     let state_with_dir = Arc::new(AppStateWithDir {
         shutdown_signal: shutdown_signal.clone(),
-        preferred_language: args.preferred_language.clone(),
+        voice_settings: Mutex::new(VoiceSettings {
+            voice: args.voice.clone(),
+            model: providers.realtime.model.clone(),
+            language: args.preferred_language.clone(),
+        }),
         project_dir: args.project_dir.clone(),
-        model: args.model.clone(),
+        providers: providers.clone(),
         instructions: args.instructions.clone(),
-        voice: args.voice.clone(),
+        persona: args.persona.clone(),
         code_model: args.code_model.clone(),
+        escalation_model: args.escalation_model.clone(),
         activity_mode: Arc::new(Mutex::new(ActivityMode::Planning)), // Default to Planning mode
+        review: !args.no_review,
+        tdd: args.tdd,
+        needs_human_reason: Mutex::new(None),
+        usage: UsageTracker::new(args.budget, args.role_budgets.iter().cloned().collect()),
+        role_states: Mutex::new(BTreeMap::new()),
+        chat_history: Mutex::new(Vec::new()),
+        repo_lock: RepoLock::new(&args.project_dir, events.clone()),
+        jobs: Jobs::default(),
+        events,
+        commentary: Verbosity::parse(&args.commentary),
+        briefing: ProjectBriefing::default(),
     });
 
     // Start ProductManager thread
@@ -400,18 +628,54 @@ async fn main() {
         .await;
     });
 
-    // Start Developer thread
-    let developer_shutdown = shutdown_signal.clone();
+    // Start QuestionTracker thread
+    let question_tracker_shutdown = shutdown_signal.clone();
     let project_dir_clone = args.project_dir.clone();
     let state_with_dir_clone = state_with_dir.clone();
     tokio::spawn(async move {
-        developer_loop(
+        question_tracker_loop(
             project_dir_clone,
-            developer_shutdown,
+            question_tracker_shutdown,
             state_with_dir_clone,
         )
         .await;
     });
+
+    // Keep open voice sessions briefed on the project
+    tokio::spawn(session::project_context_loop(shutdown_signal.clone(), state_with_dir.clone()));
+
+    // Start Developer thread, or a pool of worktree workers
+    if args.workers > 1 {
+        let pool = Arc::new(WorkerPool::new());
+        for worker_id in 1..=args.workers {
+            let worker_shutdown = shutdown_signal.clone();
+            let project_dir_clone = args.project_dir.clone();
+            let state_with_dir_clone = state_with_dir.clone();
+            let pool_clone = pool.clone();
+            tokio::spawn(async move {
+                developer_worker_loop(
+                    worker_id,
+                    project_dir_clone,
+                    worker_shutdown,
+                    state_with_dir_clone,
+                    pool_clone,
+                )
+                .await;
+            });
+        }
+    } else {
+        let developer_shutdown = shutdown_signal.clone();
+        let project_dir_clone = args.project_dir.clone();
+        let state_with_dir_clone = state_with_dir.clone();
+        tokio::spawn(async move {
+            developer_loop(
+                project_dir_clone,
+                developer_shutdown,
+                state_with_dir_clone,
+            )
+            .await;
+        });
+    }
     let project_dir = args.project_dir.clone();
     let app = Router::new()
         .route("/", get(|| async { Html(include_str!("html/index.html")) }))
@@ -425,13 +689,26 @@ async fn main() {
             }),
         )
         .route("/api/sessions", post(create_session))
+        .route("/chat", post(handle_chat))
         .route("/contexts", get(move || get_contexts(project_dir.clone())))
         .route("/change-code", post(handle_change_code))
         .route("/ask-question", post(handle_question))
+        .route("/jobs", get(get_jobs))
+        .route("/jobs/:id", get(get_job))
+        .route("/events", get(events::stream_events))
+        .route("/personas", get(get_personas))
+        .route("/personas/:name", get(get_persona))
+        .route("/voice-settings", get(get_voice_settings).post(handle_voice_settings))
         .route("/web-search", post(handle_web_search))
         .route("/update-transcript", post(handle_transcript_update))
         .route("/toggle-mode", post(handle_toggle_mode))
         .route("/current-mode", get(get_current_mode))
+        .route("/status", get(get_status))
+        .route("/tasks", get(get_tasks))
+        .route("/questions", get(get_questions))
+        .route("/needs-human-reason", get(get_needs_human_reason))
+        .route("/usage", get(get_usage))
+        .route("/usage/realtime", post(handle_realtime_usage))
         .with_state(state_with_dir.clone());
 
     println!("{}", "          /\\          ".bright_cyan());
@@ -455,13 +732,22 @@ async fn main() {
         "Language:".bright_green(),
         args.preferred_language.yellow()
     );
-    println!("{} {}", "Voice model:".bright_green(), args.model.yellow());
+    println!("{} {}", "Voice model:".bright_green(), providers.realtime.model.yellow());
     println!("{} {}", "Voice:".bright_green(), args.voice.yellow());
+    println!(
+        "{} {}",
+        "Persona:".bright_green(),
+        args.persona.as_deref().unwrap_or("by mode").yellow()
+    );
 
     if let Some(code_model) = &args.code_model {
         println!("{} {}", "Code model:".bright_green(), code_model.yellow());
     }
 
+    if args.workers > 1 {
+        println!("{} {}", "Developer workers:".bright_green(), args.workers.to_string().yellow());
+    }
+
     println!(
         "{} {}",
         "Project directory:".bright_green(),
@@ -485,62 +771,94 @@ async fn main() {
     }
 }
 
+/// The context file to `--load`, refusing anything but a `CONTEXT_*.md` file in the project.
+fn context_file(context: &str, project_dir: &str) -> Result<Option<String>, (StatusCode, Json<ErrorResponse>)> {
+    if context.is_empty() || context == "None" {
+        return Ok(None);
+    }
+    if !context.starts_with("CONTEXT_") || !context.ends_with(".md") || context.contains(['/', '\\']) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("Unknown context: {}", context),
+                project_dir: project_dir.to_string(),
+            }),
+        ));
+    }
+    Ok(Some(context.to_string()))
+}
+
 async fn handle_change_code(
     State(state_with_dir): State<Arc<AppStateWithDir>>,
     Json(payload): Json<ChangeCodeRequest>,
-) -> Result<Json<String>, (StatusCode, Json<ErrorResponse>)> {
-    let project_dir = &state_with_dir.project_dir;
-
-    let mut cmd = Command::new("aider");
-    cmd.current_dir(project_dir)
-        .arg("--no-suggest-shell-commands")
-        .arg("--yes-always")
-        .arg("--message")
-        .arg(&payload.change);
-
-    if let Some(model) = &state_with_dir.code_model {
-        cmd.arg("--model").arg(model);
+) -> Result<Json<Job>, (StatusCode, Json<ErrorResponse>)> {
+    if state_with_dir.usage.is_paused("change_code").await {
+        return Err(budget_exceeded("change_code"));
     }
+    let context = context_file(&payload.context, &state_with_dir.project_dir)?;
+    Ok(Json(jobs::submit(&state_with_dir, JobKind::ChangeCode, &payload.change, context).await))
+}
 
-    if payload.context != "None" {
-        cmd.arg("--load").arg(&payload.context);
+/// Text alternative to the voice session: one interviewer turn through a chat-completions API.
+async fn handle_chat(
+    State(state): State<Arc<AppStateWithDir>>,
+    Json(payload): Json<ChatRequest>,
+) -> Result<Json<String>, (StatusCode, Json<ErrorResponse>)> {
+    if state.usage.is_paused("interviewer").await {
+        return Err(budget_exceeded("interviewer"));
     }
-
-    let output = cmd.output().map_err(|e| {
+    let chat_error = |error: String| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
-                error: format!("Failed to execute aider: {}", e),
-                project_dir: project_dir.clone(),
+                error,
+                project_dir: state.project_dir.clone(),
             }),
         )
-    })?;
+    };
 
-    if output.status.success() {
-        Ok(Json(String::from_utf8_lossy(&output.stdout).to_string()))
-    } else {
-        Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!(
-                    "Aider command failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ),
-                project_dir: project_dir.clone(),
-            }),
-        ))
-    }
+    // Hold the history for the whole turn so concurrent messages stay in order
+    let mut history = state.chat_history.lock().await;
+    let persona = session_persona(&state, None).await.map_err(chat_error)?;
+    let mut messages = vec![ChatMessage::new("system", &persona.instructions)];
+    messages.extend(history.iter().cloned());
+    messages.push(ChatMessage::new("user", &payload.message));
+
+    let (reply, usage) = chat::complete(&state.providers.chat, &messages)
+        .await
+        .map_err(chat_error)?;
+    state.usage.record_chat(&state.providers.chat.model, &usage).await;
+
+    history.push(ChatMessage::new("user", &payload.message));
+    history.push(ChatMessage::new("assistant", &reply));
+    // The browser adds the exchange to its conversation, which it saves as TRANSCRIPT.md
+
+    Ok(Json(reply))
 }
 
 async fn get_current_mode(
     State(state): State<Arc<AppStateWithDir>>,
 ) -> Json<String> {
     let mode = state.activity_mode.lock().await;
-    Json(match *mode {
-        ActivityMode::Planning => "planning".to_string(),
-        ActivityMode::Developing => "developing".to_string(),
-        ActivityMode::ErrorNeedsHuman => "error".to_string(),
-    })
+    Json(status::mode_name(&mode).to_string())
+}
+
+async fn get_status(State(state): State<Arc<AppStateWithDir>>) -> Json<status::StatusReport> {
+    Json(status::collect(&state).await)
+}
+
+async fn get_tasks(State(state): State<Arc<AppStateWithDir>>) -> Json<Vec<tasks::Task>> {
+    Json(tasks::read_tasks(&state.project_dir))
+}
+
+async fn get_questions(State(state): State<Arc<AppStateWithDir>>) -> Json<Vec<questions::Question>> {
+    Json(questions::read_questions(&state.project_dir))
+}
+
+async fn get_needs_human_reason(
+    State(state): State<Arc<AppStateWithDir>>,
+) -> Json<Option<String>> {
+    Json(state.needs_human_reason.lock().await.clone())
 }
 
 async fn handle_toggle_mode(
@@ -563,6 +881,7 @@ async fn handle_toggle_mode(
 
     let mut mode = state.activity_mode.lock().await;
     *mode = new_mode;
+    *state.needs_human_reason.lock().await = None;
 
     Ok(Json(format!("Mode changed to {}", payload.mode)))
 }
@@ -606,44 +925,92 @@ mod architect;
 mod project_manager;
 mod tester;
 mod developer;
+mod aider;
+mod chat;
+mod diagnostics;
+mod events;
+mod git;
+mod jobs;
+mod progress;
+mod personas;
+mod providers;
+mod question_tracker;
+mod questions;
+mod repo_lock;
+mod reviewer;
+mod run_log;
+mod search;
+mod session;
+mod status;
+mod usage;
+mod voice;
+mod tasks;
+mod workers;
 
 use product_manager::product_manager_loop;
 use architect::architect_loop;
 use project_manager::project_manager_loop;
 use tester::tester_loop;
+use question_tracker::question_tracker_loop;
 use developer::developer_loop;
+use chat::ChatMessage;
+use events::{Event, Verbosity};
+use jobs::{Job, JobKind, Jobs};
+use personas::Persona;
+use providers::Providers;
+use repo_lock::RepoLock;
+use search::SearchResult;
+use session::ProjectBriefing;
+use usage::{UsageEntry, UsageReport, UsageTracker};
+use voice::{VoiceSettings, VoiceSettingsUpdate};
+use workers::{developer_worker_loop, WorkerPool};
 
 async fn handle_question(
     State(state_with_dir): State<Arc<AppStateWithDir>>,
     Json(payload): Json<QuestionRequest>,
-) -> Json<String> {
-    let project_dir = &state_with_dir.project_dir;
-
-    let mut cmd = Command::new("aider");
-    cmd.current_dir(project_dir)
-        .arg("--no-suggest-shell-commands")
-        .arg("--yes-always")
-        .arg("--message")
-        .arg(&payload.question);
-
-    if let Some(model) = &state_with_dir.code_model {
-        cmd.arg("--model").arg(model);
-    }
-
-    if payload.context != "None" {
-        cmd.arg("--load").arg(&payload.context);
+) -> Result<Json<Job>, (StatusCode, Json<ErrorResponse>)> {
+    if state_with_dir.usage.is_paused("question").await {
+        return Err(budget_exceeded("question"));
     }
+    let context = context_file(&payload.context, &state_with_dir.project_dir)?;
+    Ok(Json(jobs::submit(&state_with_dir, JobKind::Question, &payload.question, context).await))
+}
 
-    let output = cmd.output().expect("Failed to execute aider");
+async fn get_personas(State(state): State<Arc<AppStateWithDir>>) -> Json<Vec<Persona>> {
+    Json(personas::available(&state.project_dir, state.instructions.as_deref()))
+}
 
-    let response_message = if output.status.success() {
-        String::from_utf8_lossy(&output.stdout).to_string()
-    } else {
-        format!(
-            "Failed to get response from aider: {}",
-            String::from_utf8_lossy(&output.stderr)
+/// `GET /personas/<name>`: the persona ready to use as instructions, `auto` picks it by mode.
+async fn get_persona(
+    State(state): State<Arc<AppStateWithDir>>,
+    Path(name): Path<String>,
+) -> Result<Json<Persona>, (StatusCode, Json<ErrorResponse>)> {
+    session_persona(&state, Some(&name)).await.map(Json).map_err(|error| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error,
+                project_dir: state.project_dir.clone(),
+            }),
         )
-    };
+    })
+}
+
+async fn get_jobs(State(state): State<Arc<AppStateWithDir>>) -> Json<Vec<Job>> {
+    Json(state.jobs.list().await)
+}
 
-    Json(response_message)
+async fn get_job(
+    State(state): State<Arc<AppStateWithDir>>,
+    Path(id): Path<u64>,
+) -> Result<Json<Job>, (StatusCode, Json<ErrorResponse>)> {
+    state.jobs.get(id).await.map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!("No job {}", id),
+                project_dir: state.project_dir.clone(),
+            }),
+        )
+    })
 }
@@ -0,0 +1,102 @@
+use crate::ActivityMode;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+// Project personas add to the bundled ones, or replace one with the same file name
+const PERSONA_DIR: &str = ".colossus/personas";
+
+const BUNDLED: [(&str, &str); 5] = [
+    ("interviewer", include_str!("personas/interviewer.md")),
+    ("architect", include_str!("personas/architect.md")),
+    ("qa", include_str!("personas/qa.md")),
+    ("standup", include_str!("personas/standup.md")),
+    ("debugger", include_str!("personas/debugger.md")),
+];
+
+/// Instructions the voice assistant takes on, e.g. the product interviewer.
+///
+/// Project personas live in `.colossus/personas/<name>.md`; the title comes from the
+/// `<name>` tag when there is one.
+#[derive(Serialize, Clone)]
+pub struct Persona {
+    pub name: String,
+    pub title: String,
+    pub instructions: String,
+}
+
+impl Persona {
+    fn new(name: &str, instructions: &str) -> Persona {
+        let title = instructions
+            .split_once("<name>")
+            .and_then(|(_, rest)| rest.split_once("</name>"))
+            .map(|(title, _)| title.trim().to_string())
+            .unwrap_or_else(|| name.to_string());
+        Persona {
+            name: name.to_string(),
+            title,
+            instructions: instructions.trim().to_string(),
+        }
+    }
+}
+
+/// The persona a session takes on in `mode` unless one was chosen.
+pub fn for_mode(mode: &ActivityMode) -> &'static str {
+    match mode {
+        ActivityMode::Planning => "interviewer",
+        ActivityMode::Developing => "standup",
+        ActivityMode::ErrorNeedsHuman => "debugger",
+    }
+}
+
+/// Bundled personas, overridden or extended by the project's own. `instructions`, the
+/// `--instructions` flag, replaces the interviewer.
+pub fn available(project_dir: &str, instructions: Option<&str>) -> Vec<Persona> {
+    let mut personas: Vec<Persona> = BUNDLED
+        .iter()
+        .map(|(name, instructions)| Persona::new(name, instructions))
+        .collect();
+    if let Some(instructions) = instructions {
+        personas[0] = Persona::new("interviewer", instructions);
+    }
+
+    let Ok(entries) = fs::read_dir(Path::new(project_dir).join(PERSONA_DIR)) else {
+        return personas;
+    };
+    let mut files: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "md"))
+        .collect();
+    files.sort();
+    for path in files {
+        let Some(name) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+            continue;
+        };
+        let persona = match fs::read_to_string(&path) {
+            Ok(instructions) => Persona::new(&name, &instructions),
+            Err(e) => {
+                eprintln!("Skipping persona {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        match personas.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = persona,
+            None => personas.push(persona),
+        }
+    }
+    personas
+}
+
+pub fn names(personas: &[Persona]) -> String {
+    personas.iter().map(|p| p.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Finds a persona by name, case-insensitively.
+pub fn find(personas: &[Persona], name: &str) -> Result<Persona, String> {
+    personas
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+        .cloned()
+        .ok_or_else(|| format!("Unknown persona '{}'. Choose one of: {}", name, names(personas)))
+}
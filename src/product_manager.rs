@@ -1,4 +1,5 @@
-use crate::{ActivityMode, AppStateWithDir};
+use crate::events::Verbosity;
+use crate::{code_model_name, ActivityMode, AppStateWithDir};
 use filetime::FileTime;
 use std::fs;
 use std::process::Command;
@@ -31,6 +32,11 @@ pub async fn product_manager_loop(
         };
 
         if !should_continue {
+            state_with_dir.set_role_state("product_manager", "idle").await;
+            continue;
+        }
+        if state_with_dir.usage.is_paused("product_manager").await {
+            state_with_dir.set_role_state("product_manager", "paused, budget exceeded").await;
             continue;
         }
 
@@ -47,9 +53,7 @@ pub async fn product_manager_loop(
             if let (Ok(transcript_modified), Ok(project_modified)) =
                 (transcript_meta.modified(), project_meta.modified())
             {
-                let transcript_newer = transcript_modified > project_modified;
-
-                transcript_newer
+                transcript_modified > project_modified
             } else {
                 false
             }
@@ -61,9 +65,11 @@ pub async fn product_manager_loop(
             // get PROJECT.md file modified time
             let project_modified_before = fs::metadata(&project_path)
                 .map(|meta| meta.modified())
-                .unwrap_or_else(|_| Ok(std::time::SystemTime::UNIX_EPOCH))
-                .unwrap_or_else(|_| std::time::SystemTime::UNIX_EPOCH);
+                .unwrap_or(Ok(std::time::SystemTime::UNIX_EPOCH))
+                .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
 
+            let _repo = state_with_dir.repo_lock.write("product_manager").await;
+            state_with_dir.set_role_state("product_manager", "updating PROJECT.md").await;
             println!("📝 Updating PROJECT.md from transcript...");
             let mut cmd = Command::new("aider");
             cmd.current_dir(&project_dir)
@@ -89,13 +95,23 @@ pub async fn product_manager_loop(
                         String::from_utf8_lossy(&output.stderr)
                     );
                 } else {
+                    state_with_dir
+                        .usage
+                        .record_aider(
+                            "product_manager",
+                            None,
+                            code_model_name(&state_with_dir),
+                            &String::from_utf8_lossy(&output.stdout),
+                        )
+                        .await;
                     let project_modified_after = fs::metadata(&project_path)
                         .map(|meta| meta.modified())
-                        .unwrap_or_else(|_| Ok(std::time::SystemTime::UNIX_EPOCH))
-                        .unwrap_or_else(|_| std::time::SystemTime::UNIX_EPOCH);
+                        .unwrap_or(Ok(std::time::SystemTime::UNIX_EPOCH))
+                        .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
                     //check if PROJECT.md was updated
                     if project_modified_after > project_modified_before {
                         println!("✨ Aider finished updating PROJECT.md");
+                        state_with_dir.comment(Verbosity::Verbose, "PROJECT.md was updated from the conversation");
                     } else {
                         eprintln!("PROJECT.md was not updated, check the logs, but probably just empty transcript");
 
@@ -115,6 +131,7 @@ pub async fn product_manager_loop(
                 }
             }
         }
+        state_with_dir.set_role_state("product_manager", "up to date").await;
     }
     println!("ProductManagerInterview thread shutting down cleanly");
 }
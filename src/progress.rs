@@ -0,0 +1,49 @@
+use crate::git;
+use std::path::Path;
+
+// How many identical failures in a row count as going in circles
+pub const REPEAT_LIMIT: usize = 3;
+
+#[derive(PartialEq)]
+pub enum Progress {
+    Changed,
+    Unchanged,
+    Reverted,
+}
+
+/// Snapshots the working tree after every fix attempt to notice attempts that change
+/// nothing, or that undo an earlier attempt and flip back to a state already tried.
+pub struct ProgressTracker {
+    snapshots: Vec<String>,
+}
+
+impl ProgressTracker {
+    pub async fn new(dir: &Path) -> Self {
+        ProgressTracker {
+            snapshots: git::snapshot_tree(dir).await.into_iter().collect(),
+        }
+    }
+
+    pub async fn record(&mut self, dir: &Path) -> Progress {
+        let Some(tree) = git::snapshot_tree(dir).await else {
+            return Progress::Changed;
+        };
+        let progress = if self.snapshots.last() == Some(&tree) {
+            Progress::Unchanged
+        } else if self.snapshots.contains(&tree) {
+            Progress::Reverted
+        } else {
+            Progress::Changed
+        };
+        self.snapshots.push(tree);
+        progress
+    }
+}
+
+/// True when the last `REPEAT_LIMIT` failures produced exactly the same errors.
+pub fn repeated_failure(errors: &[&str]) -> bool {
+    errors.len() >= REPEAT_LIMIT
+        && errors[errors.len() - REPEAT_LIMIT..]
+            .windows(2)
+            .all(|pair| pair[0] == pair[1])
+}
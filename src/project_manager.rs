@@ -1,4 +1,5 @@
-use crate::{ActivityMode, AppStateWithDir};
+use crate::events::Verbosity;
+use crate::{code_model_name, ActivityMode, AppStateWithDir};
 use std::fs;
 use std::process::Command;
 use std::sync::Arc;
@@ -30,6 +31,11 @@ pub async fn project_manager_loop(
         };
 
         if !should_continue {
+            state_with_dir.set_role_state("project_manager", "idle").await;
+            continue;
+        }
+        if state_with_dir.usage.is_paused("project_manager").await {
+            state_with_dir.set_role_state("project_manager", "paused, budget exceeded").await;
             continue;
         }
 
@@ -63,6 +69,8 @@ pub async fn project_manager_loop(
         };
 
         if should_run_aider {
+            let _repo = state_with_dir.repo_lock.write("project_manager").await;
+            state_with_dir.set_role_state("project_manager", "updating TASKS.md").await;
             println!("📋 Updating TASKS.md from architecture...");
             let mut cmd = Command::new("aider");
             cmd.current_dir(&project_dir)
@@ -80,7 +88,8 @@ pub async fn project_manager_loop(
 8. Mark tasks that are critical path for testing
 9. Include estimates of time required for each task
 10. Ensure the sequence leads to a testable product by end of day
-11. Only add a checkmark (✓) to tasks that are confirmed complete - do not add checkmarks to new or uncertain tasks")
+11. Only add a checkmark (✓) to tasks that are confirmed complete - do not add checkmarks to new or uncertain tasks
+12. Number every task and note which earlier tasks it needs with a line like `Depends on: 1, 3` (or `Depends on: none`) so independent tasks can be worked on in parallel. A task without a `Depends on` line waits for the task listed before it, so write `Depends on: none` for every task that can start right away")
                 .arg("PROJECT.md")
                 .arg("ARCHITECTURE.md")
                 .arg("TASKS.md");
@@ -100,10 +109,21 @@ pub async fn project_manager_loop(
                         String::from_utf8_lossy(&output.stderr)
                     );
                 } else {
+                    state_with_dir
+                        .usage
+                        .record_aider(
+                            "project_manager",
+                            None,
+                            code_model_name(&state_with_dir),
+                            &String::from_utf8_lossy(&output.stdout),
+                        )
+                        .await;
                     println!("✨ Aider finished updating TASKS.md");
+                    state_with_dir.comment(Verbosity::Verbose, "TASKS.md was updated from the architecture");
                 }
             }
         }
+        state_with_dir.set_role_state("project_manager", "up to date").await;
     }
     println!("ProjectManager thread shutting down cleanly");
 }
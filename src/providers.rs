@@ -0,0 +1,173 @@
+use crate::search::SearchOptions;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+pub const PERPLEXITY_BASE_URL: &str = "https://api.perplexity.ai";
+pub const DEFAULT_REALTIME_MODEL: &str = "gpt-4o-realtime-preview-2024-12-17";
+pub const DEFAULT_CHAT_MODEL: &str = "gpt-4o-mini";
+pub const DEFAULT_SEARCH_MODEL: &str = "llama-3.1-sonar-small-128k-online";
+
+// Looked up in the project when no --providers file is given
+const PROVIDERS_FILE: &str = ".colossus/providers.json";
+
+/// Where and how one kind of HTTP model call is sent.
+#[derive(Clone, Debug)]
+pub struct Provider {
+    pub base_url: String,
+    /// Environment variable holding the API key
+    pub api_key_env: String,
+    /// `Authorization` sends `Bearer <key>`, any other header (e.g. Azure's `api-key`) the bare key
+    pub auth_header: String,
+    pub model: String,
+    /// Extra headers sent with every request
+    pub headers: BTreeMap<String, String>,
+    /// Query parameters added to every request, e.g. Azure's `api-version`
+    pub query: BTreeMap<String, String>,
+    /// Replacement paths or full URLs for endpoints that live elsewhere, e.g. `realtime`
+    pub paths: BTreeMap<String, String>,
+}
+
+/// The fields of a provider a providers.json entry may override.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ProviderConfig {
+    base_url: Option<String>,
+    api_key_env: Option<String>,
+    auth_header: Option<String>,
+    model: Option<String>,
+    headers: BTreeMap<String, String>,
+    query: BTreeMap<String, String>,
+    paths: BTreeMap<String, String>,
+}
+
+/// The search entry also says how to search, see `SearchOptions`.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SearchConfig {
+    #[serde(flatten)]
+    provider: ProviderConfig,
+    #[serde(flatten)]
+    options: SearchOptions,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ProvidersConfig {
+    realtime: ProviderConfig,
+    chat: ProviderConfig,
+    search: SearchConfig,
+}
+
+impl Provider {
+    fn new(base_url: &str, api_key_env: &str, model: &str) -> Self {
+        Provider {
+            base_url: base_url.to_string(),
+            api_key_env: api_key_env.to_string(),
+            auth_header: "Authorization".to_string(),
+            model: model.to_string(),
+            headers: BTreeMap::new(),
+            query: BTreeMap::new(),
+            paths: BTreeMap::new(),
+        }
+    }
+
+    fn apply(&mut self, config: ProviderConfig) {
+        if let Some(base_url) = config.base_url {
+            self.base_url = base_url;
+        }
+        if let Some(api_key_env) = config.api_key_env {
+            self.api_key_env = api_key_env;
+        }
+        if let Some(auth_header) = config.auth_header {
+            self.auth_header = auth_header;
+        }
+        if let Some(model) = config.model {
+            self.model = model;
+        }
+        self.headers.extend(config.headers);
+        self.query.extend(config.query);
+        self.paths.extend(config.paths);
+    }
+
+    /// The API key, unless it is unset or empty.
+    pub fn api_key(&self) -> Option<String> {
+        std::env::var(&self.api_key_env)
+            .ok()
+            .filter(|key| !key.trim().is_empty())
+    }
+
+    /// The full URL of an endpoint such as `chat/completions`, without the query.
+    pub fn url(&self, path: &str) -> String {
+        let path = self.paths.get(path).map(|p| p.as_str()).unwrap_or(path);
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return path.to_string();
+        }
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+    }
+
+    /// A request to `path` with the provider's auth, headers and query parameters.
+    pub fn request(&self, client: &reqwest::Client, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let mut request = client.request(method, self.url(path)).query(&self.query);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        match self.api_key() {
+            Some(key) if self.auth_header.eq_ignore_ascii_case("authorization") => request.bearer_auth(key),
+            Some(key) => request.header(&self.auth_header, key),
+            None => request,
+        }
+    }
+}
+
+/// The providers behind voice sessions, text chat and web search.
+#[derive(Clone, Debug)]
+pub struct Providers {
+    pub realtime: Provider,
+    pub chat: Provider,
+    pub search: Provider,
+    pub search_options: SearchOptions,
+}
+
+impl Default for Providers {
+    fn default() -> Self {
+        // Text chat has its own key so it can point at another server, but falls back to OpenAI's
+        let chat_key = if std::env::var("CHAT_API_KEY").is_ok() {
+            "CHAT_API_KEY"
+        } else {
+            "OPENAI_API_KEY"
+        };
+        Providers {
+            realtime: Provider::new(OPENAI_BASE_URL, "OPENAI_API_KEY", DEFAULT_REALTIME_MODEL),
+            chat: Provider::new(OPENAI_BASE_URL, chat_key, DEFAULT_CHAT_MODEL),
+            search: Provider::new(PERPLEXITY_BASE_URL, "PERPLEXITY_API_KEY", DEFAULT_SEARCH_MODEL),
+            search_options: SearchOptions::default(),
+        }
+    }
+}
+
+impl Providers {
+    /// The defaults overlaid with `path`, or with `.colossus/providers.json` in the project
+    /// when no path is given and that file exists.
+    pub fn load(path: Option<&str>, project_dir: &str) -> Result<Providers, String> {
+        let mut providers = Providers::default();
+        let default_path = Path::new(project_dir).join(PROVIDERS_FILE);
+        let path = match path {
+            Some(path) => Path::new(path).to_path_buf(),
+            None if default_path.exists() => default_path,
+            None => return Ok(providers),
+        };
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read providers file '{}': {}", path.display(), e))?;
+        let config: ProvidersConfig = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid providers file '{}': {}", path.display(), e))?;
+        providers.realtime.apply(config.realtime);
+        providers.chat.apply(config.chat);
+        providers.search.apply(config.search.provider);
+        providers.search_options = config.search.options;
+        Ok(providers)
+    }
+}
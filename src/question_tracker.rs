@@ -0,0 +1,141 @@
+use crate::events::{Event, Verbosity};
+use crate::questions::{self, QUESTIONS_FILE};
+use crate::{code_model_name, ActivityMode, AppStateWithDir};
+use filetime::FileTime;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Keeps QUESTIONS.md in step with the interview: what is still unresolved, what was
+/// assumed, and what has been answered since.
+pub async fn question_tracker_loop(
+    project_dir: String,
+    shutdown_signal: Arc<Mutex<bool>>,
+    state_with_dir: Arc<AppStateWithDir>,
+) {
+    let mut interval = time::interval(Duration::from_secs(30));
+
+    loop {
+        interval.tick().await;
+
+        // Check if we should shutdown
+        {
+            let shutdown = shutdown_signal.lock().await;
+            if *shutdown {
+                break;
+            }
+        }
+
+        // Questions only come up while planning
+        let should_continue = {
+            let mode = state_with_dir.activity_mode.lock().await;
+            matches!(*mode, ActivityMode::Planning)
+        };
+
+        if !should_continue {
+            state_with_dir.set_role_state("question_tracker", "idle").await;
+            continue;
+        }
+        if state_with_dir.usage.is_paused("question_tracker").await {
+            state_with_dir.set_role_state("question_tracker", "paused, budget exceeded").await;
+            continue;
+        }
+
+        let transcript_path = Path::new(&project_dir).join("TRANSCRIPT.md");
+        let questions_path = Path::new(&project_dir).join(QUESTIONS_FILE);
+
+        // Nothing to extract until the interview has started
+        let should_run_aider = match (modified(&transcript_path), modified(&questions_path)) {
+            (Some(_), None) => fs::metadata(&transcript_path).map(|m| m.len() > 0).unwrap_or(false),
+            (Some(transcript_modified), Some(questions_modified)) => transcript_modified > questions_modified,
+            (None, _) => false,
+        };
+
+        if should_run_aider {
+            let questions_modified_before = modified(&questions_path);
+
+            let _repo = state_with_dir.repo_lock.write("question_tracker").await;
+            state_with_dir
+                .set_role_state("question_tracker", "updating QUESTIONS.md")
+                .await;
+            println!("❓ Updating QUESTIONS.md from transcript...");
+            let mut cmd = Command::new("aider");
+            cmd.current_dir(&project_dir)
+                .arg("--no-suggest-shell-commands")
+                .arg("--yes-always")
+                .arg("--message")
+                .arg(format!(
+                    "given the TRANSCRIPT.md, update QUESTIONS.md with the questions about the project that are \
+still unresolved and the assumptions that were made without asking. Mark questions the conversation has \
+answered since as answered, keep answered ones, don't repeat a question and put the most important open \
+ones first. Write {}. Only edit QUESTIONS.md.",
+                    questions::FORMAT
+                ))
+                // The transcript is the record of the interview, only QUESTIONS.md may change
+                .arg("--read")
+                .arg("TRANSCRIPT.md")
+                .arg(QUESTIONS_FILE);
+
+            if let Some(model) = &state_with_dir.code_model {
+                cmd.arg("--model").arg(model);
+            }
+
+            let output = cmd.output().await.map_err(|e| {
+                eprintln!("Failed to run aider: {}", e);
+            });
+
+            if let Ok(output) = output {
+                if !output.status.success() {
+                    eprintln!(
+                        "Aider command failed: {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                } else {
+                    state_with_dir
+                        .usage
+                        .record_aider(
+                            "question_tracker",
+                            None,
+                            code_model_name(&state_with_dir),
+                            &String::from_utf8_lossy(&output.stdout),
+                        )
+                        .await;
+                    if modified(&questions_path) > questions_modified_before {
+                        let questions = questions::read_questions(&project_dir);
+                        let open = questions
+                            .iter()
+                            .filter(|q| q.status == questions::QuestionStatus::Open)
+                            .count();
+                        println!("✨ Aider finished updating QUESTIONS.md, {} open", open);
+                        state_with_dir.comment(
+                            Verbosity::Verbose,
+                            &format!("QUESTIONS.md was updated from the conversation, {} questions are open", open),
+                        );
+                        state_with_dir.emit(Event::Questions { questions });
+                    } else {
+                        eprintln!("QUESTIONS.md was not updated, probably nothing new in the transcript");
+
+                        // Mark the transcript as seen so it isn't read again until it changes
+                        let result = match questions_path.exists() {
+                            true => filetime::set_file_mtime(&questions_path, FileTime::now()),
+                            false => fs::write(&questions_path, "# Questions\n"),
+                        };
+                        if let Err(e) = result {
+                            eprintln!("Failed to update {}: {}", QUESTIONS_FILE, e);
+                        }
+                    }
+                }
+            }
+        }
+        state_with_dir.set_role_state("question_tracker", "up to date").await;
+    }
+    println!("QuestionTracker thread shutting down cleanly");
+}
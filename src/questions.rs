@@ -0,0 +1,133 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+pub const QUESTIONS_FILE: &str = "QUESTIONS.md";
+
+// How the question tracker is asked to write QUESTIONS.md, and what `parse_questions` reads
+pub const FORMAT: &str = "one line per item: `- [ ] the question` while it is open, \
+`- [x] the question Answer: the answer` once the conversation settled it, \
+and `Assumption:` before the question for things that were assumed rather than asked";
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestionStatus {
+    Open,
+    Answered,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Question {
+    pub text: String,
+    pub status: QuestionStatus,
+    /// Something taken for granted in the interview that the user should confirm
+    pub assumption: bool,
+    pub answer: Option<String>,
+}
+
+// Parses "- [ ] Who are the users?" or "* [x] Assumption: Web only Answer: yes"
+fn parse_question(line: &str) -> Option<Question> {
+    let rest = line.trim_start().strip_prefix(['-', '*'])?.trim_start();
+    let (status, rest) = if let Some(rest) = rest.strip_prefix("[ ]") {
+        (QuestionStatus::Open, rest)
+    } else if let Some(rest) = rest.strip_prefix("[x]").or_else(|| rest.strip_prefix("[X]")) {
+        (QuestionStatus::Answered, rest)
+    } else {
+        return None;
+    };
+
+    // Models like to put the markers in bold
+    let rest = rest.replace("**Assumption:**", "Assumption:").replace("**Answer:**", "Answer:");
+    let rest = rest.trim();
+    let (rest, assumption) = match rest.strip_prefix("Assumption:") {
+        Some(rest) => (rest.trim_start(), true),
+        None => (rest, false),
+    };
+    let (text, answer) = match rest.split_once("Answer:") {
+        Some((text, answer)) => (text.trim(), Some(answer.trim().to_string())),
+        None => (rest, None),
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(Question {
+        text: text.to_string(),
+        status,
+        assumption,
+        answer: answer.filter(|a| !a.is_empty()),
+    })
+}
+
+pub fn parse_questions(content: &str) -> Vec<Question> {
+    content.lines().filter_map(parse_question).collect()
+}
+
+pub fn read_questions(project_dir: &str) -> Vec<Question> {
+    fs::read_to_string(Path::new(project_dir).join(QUESTIONS_FILE))
+        .map(|content| parse_questions(&content))
+        .unwrap_or_default()
+}
+
+/// The first `count` open questions, in the order QUESTIONS.md lists them.
+pub fn open_questions(project_dir: &str, count: usize) -> Vec<Question> {
+    read_questions(project_dir)
+        .into_iter()
+        .filter(|q| q.status == QuestionStatus::Open)
+        .take(count)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_open_and_answered_questions() {
+        let questions = parse_questions(
+            "# Questions
+
+## Open
+- [ ] Who are the users?
+* [ ] Assumption: The app is web only
+
+## Answered
+- [x] Which database? Answer: PostgreSQL
+- [X] Assumption: Users sign in with email Answer: yes, and with Google
+",
+        );
+        assert_eq!(questions.len(), 4);
+
+        assert_eq!(questions[0].text, "Who are the users?");
+        assert_eq!(questions[0].status, QuestionStatus::Open);
+        assert!(!questions[0].assumption);
+        assert_eq!(questions[0].answer, None);
+
+        assert_eq!(questions[1].text, "The app is web only");
+        assert!(questions[1].assumption);
+
+        assert_eq!(questions[2].text, "Which database?");
+        assert_eq!(questions[2].status, QuestionStatus::Answered);
+        assert_eq!(questions[2].answer.as_deref(), Some("PostgreSQL"));
+
+        assert_eq!(questions[3].text, "Users sign in with email");
+        assert!(questions[3].assumption);
+        assert_eq!(questions[3].answer.as_deref(), Some("yes, and with Google"));
+    }
+
+    #[test]
+    fn accepts_bold_markers() {
+        let question = parse_question("- [x] **Assumption:** Prices are in euros **Answer:** and dollars").unwrap();
+        assert!(question.assumption);
+        assert_eq!(question.text, "Prices are in euros");
+        assert_eq!(question.answer.as_deref(), Some("and dollars"));
+    }
+
+    #[test]
+    fn skips_lines_that_are_not_questions() {
+        assert!(parse_question("Who are the users?").is_none());
+        assert!(parse_question("- Who are the users?").is_none());
+        assert!(parse_question("- [ ]").is_none());
+        assert!(parse_question("- [ ] Assumption: ").is_none());
+        assert_eq!(parse_question("- [x] Which database? Answer:").unwrap().answer, None);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::events::Event;
+use chrono::Local;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::{broadcast, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// Locked with fs2 so Colossus processes serving the same project take turns too
+const LOCK_FILE: &str = ".colossus/repo.lock";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Holder {
+    #[serde(skip)]
+    id: u64,
+    pub who: String,
+    pub access: Access,
+    pub since: String,
+}
+
+/// Serializes everything that runs aider on the working tree: any number of readers,
+/// or a single writer.
+pub struct RepoLock {
+    lock: RwLock<()>,
+    path: PathBuf,
+    holders: Mutex<Vec<Holder>>,
+    next_id: AtomicU64,
+    events: broadcast::Sender<Event>,
+}
+
+/// Holds the repository until dropped.
+pub struct RepoGuard<'a> {
+    owner: &'a RepoLock,
+    id: u64,
+    // Fields drop in order, so other processes get the file lock before this one's waiters run
+    _file: Option<File>,
+    _read: Option<RwLockReadGuard<'a, ()>>,
+    _write: Option<RwLockWriteGuard<'a, ()>>,
+}
+
+impl Drop for RepoGuard<'_> {
+    fn drop(&mut self) {
+        self.owner.holders.lock().unwrap().retain(|h| h.id != self.id);
+        self.owner.publish();
+    }
+}
+
+impl RepoLock {
+    pub fn new(project_dir: &str, events: broadcast::Sender<Event>) -> Self {
+        RepoLock {
+            lock: RwLock::new(()),
+            path: Path::new(project_dir).join(LOCK_FILE),
+            holders: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            events,
+        }
+    }
+
+    /// Waits until `who` is the only one on the working tree.
+    pub async fn write(&self, who: &str) -> RepoGuard<'_> {
+        let write = self.lock.write().await;
+        self.acquired(who, Access::Write, None, Some(write)).await
+    }
+
+    /// Waits until no one is writing; other readers may run alongside `who`.
+    pub async fn read(&self, who: &str) -> RepoGuard<'_> {
+        let read = self.lock.read().await;
+        self.acquired(who, Access::Read, Some(read), None).await
+    }
+
+    pub fn holders(&self) -> Vec<Holder> {
+        self.holders.lock().unwrap().clone()
+    }
+
+    async fn acquired<'a>(
+        &'a self,
+        who: &str,
+        access: Access,
+        read: Option<RwLockReadGuard<'a, ()>>,
+        write: Option<RwLockWriteGuard<'a, ()>>,
+    ) -> RepoGuard<'a> {
+        let file = self.lock_file(who, access).await;
+        let holder = Holder {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            who: who.to_string(),
+            access,
+            since: Local::now().to_rfc3339(),
+        };
+        let id = holder.id;
+        self.holders.lock().unwrap().push(holder);
+        self.publish();
+        RepoGuard {
+            owner: self,
+            id,
+            _file: file,
+            _read: read,
+            _write: write,
+        }
+    }
+
+    // The cross-process half; without a lock file only this process is serialized
+    async fn lock_file(&self, who: &str, access: Access) -> Option<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).ok()?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.path)
+            .map_err(|e| eprintln!("Failed to open {}: {}", self.path.display(), e))
+            .ok()?;
+
+        let locked = match access {
+            Access::Read => FileExt::try_lock_shared(&file),
+            Access::Write => FileExt::try_lock_exclusive(&file),
+        };
+        if locked.is_ok() {
+            return Some(file);
+        }
+        println!("⏳ {} is waiting for another Colossus process working in this project", who);
+        tokio::task::spawn_blocking(move || {
+            let locked = match access {
+                Access::Read => FileExt::lock_shared(&file),
+                Access::Write => FileExt::lock_exclusive(&file),
+            };
+            locked.map(|_| file)
+        })
+        .await
+        .ok()?
+        .map_err(|e| eprintln!("Failed to lock the repository: {}", e))
+        .ok()
+    }
+
+    fn publish(&self) {
+        let _ = self.events.send(Event::RepoLock { holders: self.holders() });
+    }
+}
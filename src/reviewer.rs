@@ -0,0 +1,181 @@
+use crate::developer::build_and_test;
+use crate::{aider, git};
+use crate::usage::UsageContext;
+use crate::AppStateWithDir;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+const REVIEW_DIR: &str = ".colossus/review";
+const MAX_REVIEW_ROUNDS: usize = 3;
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Approve,
+    RequestChanges,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Review {
+    pub verdict: Verdict,
+    #[serde(default)]
+    pub comments: Vec<String>,
+}
+
+// Aider surrounds the answer with its own chatter, so take the last JSON object that
+// looks like a review.
+fn parse_review(output: &str) -> Option<Review> {
+    output
+        .match_indices('{')
+        .map(|(i, _)| i)
+        .rev()
+        .find_map(|i| {
+            serde_json::Deserializer::from_str(&output[i..])
+                .into_iter::<Review>()
+                .next()
+                .and_then(|r| r.ok())
+        })
+}
+
+/// Asks the reviewer for a verdict on everything changed in `dir` since `base`.
+pub async fn review_changes(
+    dir: &Path,
+    base: &str,
+    task: &str,
+    model: &str,
+    usage: &UsageContext<'_>,
+) -> Result<Review, String> {
+    // Run state and the review files themselves are not part of the task
+    git::exclude_path(dir, "/.colossus/").await?;
+    let diff = git::diff_since(dir, base).await?;
+    if diff.trim().is_empty() {
+        return Ok(Review {
+            verdict: Verdict::RequestChanges,
+            comments: vec!["No changes were made for this task.".to_string()],
+        });
+    }
+
+    fs::create_dir_all(dir.join(REVIEW_DIR)).map_err(|e| format!("Failed to create review directory: {}", e))?;
+    let diff_path = format!("{}/TASK.diff", REVIEW_DIR);
+    fs::write(dir.join(&diff_path), diff).map_err(|e| format!("Failed to write task diff: {}", e))?;
+
+    let mut read_files = vec![diff_path.clone()];
+    for doc in ["ARCHITECTURE.md", "TEST_STRATEGY.md"] {
+        if dir.join(doc).exists() {
+            read_files.push(doc.to_string());
+        }
+    }
+
+    println!("🔍 Reviewing changes for {}...", task);
+    let message = format!(
+        "You are a senior code reviewer. {} contains the diff for {}. Review it against ARCHITECTURE.md and TEST_STRATEGY.md: \
+check that it implements the task, follows the architecture, includes tests, and has no obvious bugs. \
+Only request changes for real problems, not style preferences. \
+Answer with a single JSON object and nothing else: {{\"verdict\": \"approve\" or \"request_changes\", \"comments\": [\"...\"]}}",
+        diff_path, task
+    );
+    let output = aider::ask(dir, model, &message, &read_files, usage)
+        .await
+        .ok_or_else(|| "Reviewer could not run aider".to_string())?;
+
+    parse_review(&output).ok_or_else(|| "Reviewer did not return a verdict".to_string())
+}
+
+/// Reviews the task and sends change requests back to the developer until the reviewer
+/// approves, re-running build and tests after every round of fixes.
+pub async fn review_until_approved(
+    dir: &Path,
+    base: &str,
+    task: &str,
+    model: &str,
+    state_with_dir: &AppStateWithDir,
+) -> Result<(), String> {
+    let review_usage = state_with_dir.usage.context("reviewer", Some(task));
+    let developer_usage = state_with_dir.usage.context("developer", Some(task));
+
+    for round in 1..=MAX_REVIEW_ROUNDS {
+        // Unreviewed changes are never approved; a reviewer that fails twice needs a human
+        let review = match review_changes(dir, base, task, model, &review_usage).await {
+            Ok(review) => review,
+            Err(e) => {
+                eprintln!("{}, asking the reviewer again", e);
+                review_changes(dir, base, task, model, &review_usage)
+                    .await
+                    .map_err(|e| format!("Could not get a review for {}: {}", task, e))?
+            }
+        };
+
+        if review.verdict == Verdict::Approve {
+            println!("👍 Reviewer approved {}", task);
+            return Ok(());
+        }
+
+        println!("📝 Reviewer requested changes (round {} of {}):", round, MAX_REVIEW_ROUNDS);
+        for comment in &review.comments {
+            println!("  - {}", comment);
+        }
+        if round == MAX_REVIEW_ROUNDS {
+            return Err(format!(
+                "Reviewer still requested changes for {} after {} rounds: {}",
+                task,
+                MAX_REVIEW_ROUNDS,
+                review.comments.join("; ")
+            ));
+        }
+
+        let message = format!(
+            "A code reviewer requested changes to {}. Address these comments:\n- {}",
+            task,
+            review.comments.join("\n- ")
+        );
+        aider::run(dir, model, &message, &[], &developer_usage).await;
+
+        build_and_test(&dir.to_string_lossy(), task, model, state_with_dir)
+            .await
+            .map_err(|e| format!("{} while addressing review comments for {}", e, task))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_bare_verdict() {
+        let review = parse_review(r#"{"verdict": "approve", "comments": []}"#).unwrap();
+        assert_eq!(review.verdict, Verdict::Approve);
+        assert!(review.comments.is_empty());
+    }
+
+    #[test]
+    fn finds_the_verdict_in_aider_output() {
+        let output = r#"Answer with a single JSON object and nothing else: {"verdict": "approve" or "request_changes", "comments": ["..."]}
+
+Tokens: 2.1k sent, 45 received.
+```json
+{"verdict": "request_changes", "comments": ["Add a test for the empty {} case", "Handle errors"]}
+```
+"#;
+        let review = parse_review(output).unwrap();
+        assert_eq!(review.verdict, Verdict::RequestChanges);
+        assert_eq!(review.comments, ["Add a test for the empty {} case", "Handle errors"]);
+    }
+
+    #[test]
+    fn comments_are_optional() {
+        let review = parse_review(r#"Looks good. {"verdict": "approve"}"#).unwrap();
+        assert_eq!(review.verdict, Verdict::Approve);
+        assert!(review.comments.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_reviews() {
+        assert!(parse_review("").is_none());
+        assert!(parse_review("The change looks fine to me.").is_none());
+        assert!(parse_review(r#"{"verdict": "approve", "comments": ["unterminated"#).is_none());
+        assert!(parse_review(r#"{"verdict": "lgtm"}"#).is_none());
+        assert!(parse_review(r#"{"comments": ["no verdict"]}"#).is_none());
+    }
+}
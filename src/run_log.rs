@@ -0,0 +1,35 @@
+use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+const RUN_LOG: &str = ".colossus/run.log";
+
+/// Appends a timestamped line to the project's run log.
+pub fn record(project_dir: &Path, message: &str) {
+    let log_path = project_dir.join(RUN_LOG);
+    if let Some(parent) = log_path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+
+    let line = format!("[{}] {}\n", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+
+    if let Err(e) = result {
+        eprintln!("Failed to write run log: {}", e);
+    }
+}
+
+/// The last `count` lines of the run log, oldest first.
+pub fn recent(project_dir: &Path, count: usize) -> Vec<String> {
+    let content = fs::read_to_string(project_dir.join(RUN_LOG)).unwrap_or_default();
+    let lines: Vec<&str> = content.lines().collect();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
@@ -0,0 +1,368 @@
+use crate::providers::Provider;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// Paragraphs shorter than this are headings or list fragments, not worth citing
+const MIN_PARAGRAPH_LEN: usize = 40;
+const MAX_INDEXED_FILE_SIZE: u64 = 512 * 1024;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Perplexity,
+    /// SearxNG, or any endpoint answering `search?q=...&format=json` with the same `results`
+    Searxng,
+    Local,
+}
+
+/// How web search is done, read from the `search` entry of the providers file.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub backend: Backend,
+    /// Only search these domains, e.g. `docs.rs`; empty searches everywhere
+    pub domains: Vec<String>,
+    /// day, week, month or year
+    pub recency: Option<String>,
+    /// Directories and files of the project searched by the local index
+    pub index: Vec<String>,
+    /// Number of results to cite
+    pub results: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            backend: Backend::Perplexity,
+            domains: Vec::new(),
+            recency: None,
+            index: vec![".".to_string()],
+            results: 5,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Citation {
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchResult {
+    pub answer: String,
+    pub citations: Vec<Citation>,
+    /// Which backend answered, `local` when the web search failed and the index stood in
+    pub source: String,
+    /// Token usage reported by the backend, if any
+    #[serde(skip)]
+    pub usage: serde_json::Value,
+}
+
+pub trait SearchProvider {
+    fn name(&self) -> &'static str;
+    async fn search(&self, question: &str) -> Result<SearchResult, String>;
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+async fn send(request: reqwest::RequestBuilder, base_url: &str) -> Result<serde_json::Value, String> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", base_url, e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("API error: {} - {}", status, body));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))
+}
+
+// Whether `url` is on one of `domains` or a subdomain of one
+fn in_domains(url: &str, domains: &[String]) -> bool {
+    let host = url
+        .split("://")
+        .nth(1)
+        .unwrap_or(url)
+        .split(['/', ':', '?'])
+        .next()
+        .unwrap_or_default();
+    domains.is_empty()
+        || domains
+            .iter()
+            .any(|domain| host == domain || host.ends_with(&format!(".{}", domain)))
+}
+
+pub struct Perplexity<'a> {
+    pub provider: &'a Provider,
+    pub options: &'a SearchOptions,
+}
+
+impl SearchProvider for Perplexity<'_> {
+    fn name(&self) -> &'static str {
+        "perplexity"
+    }
+
+    async fn search(&self, question: &str) -> Result<SearchResult, String> {
+        if self.provider.api_key().is_none() {
+            return Err(format!("{} is not configured", self.provider.api_key_env));
+        }
+        let mut body = serde_json::json!({
+            "model": self.provider.model,
+            "messages": [
+                { "role": "system", "content": "Be precise and concise." },
+                { "role": "user", "content": question }
+            ],
+            "temperature": 0.2,
+            "top_p": 0.9,
+            "return_images": false,
+            "return_related_questions": false,
+            "stream": false,
+        });
+        if !self.options.domains.is_empty() {
+            body["search_domain_filter"] = serde_json::json!(self.options.domains);
+        }
+        if let Some(recency) = &self.options.recency {
+            body["search_recency_filter"] = serde_json::json!(recency);
+        }
+
+        let request = self
+            .provider
+            .request(&client()?, Method::POST, "chat/completions")
+            .json(&body);
+        let json = send(request, &self.provider.base_url).await?;
+        let answer = json["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| "Search response had no content".to_string())?
+            .to_string();
+
+        // Newer responses carry titled `search_results`, older ones only the `citations` URLs
+        let mut citations: Vec<Citation> = json["search_results"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|result| {
+                Some(Citation {
+                    title: result["title"].as_str().unwrap_or_default().to_string(),
+                    url: result["url"].as_str()?.to_string(),
+                })
+            })
+            .collect();
+        if citations.is_empty() {
+            citations = json["citations"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|url| url.as_str())
+                .map(|url| Citation {
+                    title: url.to_string(),
+                    url: url.to_string(),
+                })
+                .collect();
+        }
+        citations.truncate(self.options.results);
+
+        Ok(SearchResult {
+            answer,
+            citations,
+            source: self.name().to_string(),
+            usage: json["usage"].clone(),
+        })
+    }
+}
+
+pub struct Searxng<'a> {
+    pub provider: &'a Provider,
+    pub options: &'a SearchOptions,
+}
+
+impl SearchProvider for Searxng<'_> {
+    fn name(&self) -> &'static str {
+        "searxng"
+    }
+
+    async fn search(&self, question: &str) -> Result<SearchResult, String> {
+        let mut query = vec![("q", question.to_string()), ("format", "json".to_string())];
+        if let Some(recency) = &self.options.recency {
+            query.push(("time_range", recency.clone()));
+        }
+        let request = self
+            .provider
+            .request(&client()?, Method::GET, "search")
+            .query(&query);
+        let json = send(request, &self.provider.base_url).await?;
+
+        let results: Vec<(Citation, String)> = json["results"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|result| {
+                let url = result["url"].as_str()?;
+                Some((
+                    Citation {
+                        title: result["title"].as_str().unwrap_or(url).to_string(),
+                        url: url.to_string(),
+                    },
+                    result["content"].as_str().unwrap_or_default().trim().to_string(),
+                ))
+            })
+            .filter(|(citation, _)| in_domains(&citation.url, &self.options.domains))
+            .take(self.options.results)
+            .collect();
+        if results.is_empty() {
+            return Err(format!("No results from {}", self.provider.base_url));
+        }
+
+        // There is no model to summarize, so the answer is the numbered snippets
+        let answer = results
+            .iter()
+            .enumerate()
+            .map(|(i, (citation, content))| format!("[{}] {}: {}", i + 1, citation.title, content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(SearchResult {
+            answer,
+            citations: results.into_iter().map(|(citation, _)| citation).collect(),
+            source: self.name().to_string(),
+            usage: serde_json::Value::Null,
+        })
+    }
+}
+
+/// Searches the project's own Markdown and text files, for offline use or when the web
+/// search is unavailable.
+pub struct LocalIndex<'a> {
+    pub project_dir: &'a Path,
+    pub options: &'a SearchOptions,
+}
+
+impl LocalIndex<'_> {
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut pending: Vec<PathBuf> = self.options.index.iter().map(|p| self.project_dir.join(p)).collect();
+        while let Some(path) = pending.pop() {
+            let hidden = path
+                .file_name()
+                .map(|name| name.to_string_lossy().starts_with('.') && name != ".")
+                .unwrap_or(false);
+            if hidden || path.ends_with("target") || path.ends_with("node_modules") {
+                continue;
+            }
+            if path.is_dir() {
+                if let Ok(entries) = fs::read_dir(&path) {
+                    pending.extend(entries.flatten().map(|entry| entry.path()));
+                }
+            } else if matches!(path.extension().and_then(|e| e.to_str()), Some("md" | "txt"))
+                && fs::metadata(&path).map(|m| m.len() <= MAX_INDEXED_FILE_SIZE).unwrap_or(false)
+            {
+                files.push(path);
+            }
+        }
+        files.sort();
+        files
+    }
+}
+
+impl SearchProvider for LocalIndex<'_> {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn search(&self, question: &str) -> Result<SearchResult, String> {
+        let terms: Vec<String> = question
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| term.len() > 2)
+            .map(|term| term.to_lowercase())
+            .collect();
+        if terms.is_empty() {
+            return Err("Nothing to search for".to_string());
+        }
+
+        // Each paragraph scores one point per occurrence of a term
+        let mut matches: Vec<(usize, String, String)> = Vec::new();
+        for file in self.files() {
+            let Ok(content) = fs::read_to_string(&file) else {
+                continue;
+            };
+            let relative = file
+                .strip_prefix(self.project_dir)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .trim_start_matches("./")
+                .to_string();
+            for paragraph in content.split("\n\n").map(str::trim) {
+                if paragraph.len() < MIN_PARAGRAPH_LEN {
+                    continue;
+                }
+                let lower = paragraph.to_lowercase();
+                let score: usize = terms.iter().map(|term| lower.matches(term.as_str()).count()).sum();
+                if score > 0 {
+                    matches.push((score, relative.clone(), paragraph.to_string()));
+                }
+            }
+        }
+        if matches.is_empty() {
+            return Err("Nothing in the project matches".to_string());
+        }
+        matches.sort_by_key(|m| std::cmp::Reverse(m.0));
+        matches.truncate(self.options.results);
+
+        let answer = matches
+            .iter()
+            .enumerate()
+            .map(|(i, (_, file, paragraph))| format!("[{}] {}: {}", i + 1, file, paragraph))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Ok(SearchResult {
+            answer,
+            citations: matches
+                .into_iter()
+                .map(|(_, file, _)| Citation {
+                    title: file.clone(),
+                    url: file,
+                })
+                .collect(),
+            source: self.name().to_string(),
+            usage: serde_json::Value::Null,
+        })
+    }
+}
+
+/// Searches with the configured backend, falling back to the local index when it fails.
+pub async fn search(
+    provider: &Provider,
+    options: &SearchOptions,
+    project_dir: &Path,
+    question: &str,
+) -> Result<SearchResult, String> {
+    let local = LocalIndex { project_dir, options };
+    let web = match options.backend {
+        Backend::Perplexity => Perplexity { provider, options }.search(question).await,
+        Backend::Searxng => Searxng { provider, options }.search(question).await,
+        Backend::Local => return local.search(question).await,
+    };
+    match web {
+        Ok(result) => Ok(result),
+        Err(web_error) => {
+            println!("⚠️ Web search failed ({}), searching the project instead", web_error);
+            local
+                .search(question)
+                .await
+                .map_err(|local_error| format!("{}; {}", web_error, local_error))
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::chat::{self, ChatMessage};
+use crate::events::Event;
+use crate::questions::{self, QUESTIONS_FILE};
+use crate::{status, ActivityMode, AppStateWithDir};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+
+// Enough of the transcript to summarize, or to stand in for a summary
+const SUMMARY_INPUT_CHARS: usize = 20_000;
+const FALLBACK_TRANSCRIPT_CHARS: usize = 4_000;
+
+const SUMMARY_PROMPT: &str = "Summarize this interview between a user and an assistant planning a software project. \
+Keep the decisions made, the open questions and what was being discussed when it stopped. Be concise.";
+
+// Planning documents the voice assistant is briefed on
+const PROJECT_DOCUMENTS: [&str; 2] = ["PROJECT.md", "ARCHITECTURE.md"];
+// Also watched, since task progress and open questions are part of the briefing
+const TASKS_FILE: &str = "TASKS.md";
+const DOCUMENT_INPUT_CHARS: usize = 12_000;
+const FALLBACK_DOCUMENT_CHARS: usize = 1_500;
+// Open questions the interviewer is asked to bring up next
+const NEXT_QUESTIONS: usize = 3;
+
+const BRIEFING_PROMPT: &str = "Summarize these planning documents for a voice assistant interviewing the user \
+about their software project. In a few sentences say what is being built and the main technical choices, \
+then list the questions that are still open or undecided. Be concise, plain text, no Markdown.";
+
+// The last `count` characters of `text`, cut at a character boundary
+fn tail(text: &str, count: usize) -> &str {
+    match text.char_indices().rev().nth(count) {
+        Some((start, _)) => &text[start..],
+        None => text,
+    }
+}
+
+/// A one-line account of the mode and tasks, e.g. for instructions.
+pub async fn project_state(state: &AppStateWithDir) -> String {
+    let report = status::collect(state).await;
+    let done = report.tasks.iter().filter(|t| t.done).count();
+    let mut line = format!(
+        "Colossus is in {} mode with {} of {} tasks done.",
+        report.mode.unwrap_or_default(),
+        done,
+        report.tasks.len()
+    );
+    if let Some(task) = report.current_task {
+        line.push_str(&format!(" The next task is {}: {}.", task.label, task.title));
+    }
+    line
+}
+
+/// Summarizes TRANSCRIPT.md with the chat provider, falling back to its end when that fails.
+async fn transcript_summary(state: &AppStateWithDir) -> Option<String> {
+    let transcript = fs::read_to_string(Path::new(&state.project_dir).join("TRANSCRIPT.md")).ok()?;
+    let transcript = transcript.trim();
+    if transcript.is_empty() {
+        return None;
+    }
+
+    let messages = [
+        ChatMessage::new("system", SUMMARY_PROMPT),
+        ChatMessage::new("user", tail(transcript, SUMMARY_INPUT_CHARS)),
+    ];
+    match chat::complete(&state.providers.chat, &messages).await {
+        Ok((summary, usage)) => {
+            state.usage.record_chat(&state.providers.chat.model, &usage).await;
+            Some(summary)
+        }
+        Err(e) => {
+            eprintln!("Failed to summarize the transcript, using its end instead: {}", e);
+            Some(format!("(end of the transcript)\n{}", tail(transcript, FALLBACK_TRANSCRIPT_CHARS)))
+        }
+    }
+}
+
+/// Instructions that let a renewed or reconnected voice session carry on the interview.
+pub async fn resume_context(state: &AppStateWithDir) -> String {
+    let mut context = "This session continues an earlier conversation that was cut off. \
+Do not greet the user again or start over, pick up where it stopped."
+        .to_string();
+    if let Some(summary) = transcript_summary(state).await {
+        context.push_str(&format!("\n\nThe conversation so far:\n{}", summary));
+    }
+    context
+}
+
+// Modification times of the briefed documents, to tell when the briefing is stale
+fn modified(project_dir: &str, files: &[&str]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(Path::new(project_dir).join(file)).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// The summary of PROJECT.md and ARCHITECTURE.md, made again only once they change.
+#[derive(Default)]
+pub struct ProjectBriefing {
+    cached: Mutex<Option<(Vec<Option<SystemTime>>, String)>>,
+}
+
+impl ProjectBriefing {
+    async fn get(&self, state: &AppStateWithDir) -> Option<String> {
+        let version = modified(&state.project_dir, &PROJECT_DOCUMENTS);
+        // Held while summarizing, so sessions starting together share one request
+        let mut cached = self.cached.lock().await;
+        if let Some((cached_version, summary)) = &*cached {
+            if *cached_version == version {
+                return Some(summary.clone());
+            }
+        }
+
+        let documents: Vec<(&str, String)> = PROJECT_DOCUMENTS
+            .iter()
+            .filter_map(|file| {
+                let content = fs::read_to_string(Path::new(&state.project_dir).join(file)).ok()?;
+                let content = content.trim();
+                (!content.is_empty()).then(|| (*file, content.to_string()))
+            })
+            .collect();
+        if documents.is_empty() {
+            return None;
+        }
+
+        let input = documents
+            .iter()
+            .map(|(file, content)| format!("# {}\n{}", file, tail(content, DOCUMENT_INPUT_CHARS)))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let messages = [
+            ChatMessage::new("system", BRIEFING_PROMPT),
+            ChatMessage::new("user", &input),
+        ];
+        let summary = match chat::complete(&state.providers.chat, &messages).await {
+            Ok((summary, usage)) => {
+                state.usage.record_chat(&state.providers.chat.model, &usage).await;
+                summary
+            }
+            Err(e) => {
+                eprintln!("Failed to summarize the planning documents, using their start instead: {}", e);
+                documents
+                    .iter()
+                    .map(|(file, content)| {
+                        let start: String = content.chars().take(FALLBACK_DOCUMENT_CHARS).collect();
+                        format!("{}:\n{}", file, start)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            }
+        };
+        *cached = Some((version, summary.clone()));
+        Some(summary)
+    }
+}
+
+/// What the voice assistant should know about the project right now: a summary of the
+/// planning documents with their open questions, the mode and task progress, and while
+/// planning the questions from QUESTIONS.md to ask next.
+pub async fn project_context(state: &AppStateWithDir) -> String {
+    let mut context = String::from("Current state of the project:");
+    if let Some(briefing) = state.briefing.get(state).await {
+        context.push_str(&format!("\n{}", briefing));
+    }
+    context.push_str(&format!("\n\n{}", project_state(state).await));
+
+    let planning = matches!(*state.activity_mode.lock().await, ActivityMode::Planning);
+    let next = questions::open_questions(&state.project_dir, NEXT_QUESTIONS);
+    if planning && !next.is_empty() {
+        context.push_str("\n\nAsk about these open questions next, one at a time, when the conversation allows:");
+        for question in next {
+            let kind = if question.assumption { "Confirm the assumption: " } else { "" };
+            context.push_str(&format!("\n- {}{}", kind, question.text));
+        }
+    }
+    context
+}
+
+/// Publishes a fresh project context whenever the planning documents, the tasks or the mode
+/// change, so open voice sessions can update their instructions.
+pub async fn project_context_loop(shutdown_signal: Arc<Mutex<bool>>, state: Arc<AppStateWithDir>) {
+    let mut interval = time::interval(Duration::from_secs(10));
+    let mut last = None;
+
+    loop {
+        interval.tick().await;
+        if *shutdown_signal.lock().await {
+            break;
+        }
+
+        let mut files = PROJECT_DOCUMENTS.to_vec();
+        files.extend([TASKS_FILE, QUESTIONS_FILE]);
+        let version = (
+            modified(&state.project_dir, &files),
+            status::mode_name(&*state.activity_mode.lock().await),
+        );
+        if last.as_ref() == Some(&version) {
+            continue;
+        }
+        // The first look only records where things stand, sessions start with it anyway
+        let changed = last.is_some();
+        last = Some(version);
+        // Summarizing costs a request, so only bother while a browser is listening
+        if changed && state.events.receiver_count() > 0 {
+            state.emit(Event::ProjectContext {
+                context: project_context(&state).await,
+            });
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::cli::{ClientArgs, ModeArgs};
+use crate::repo_lock::{Access, Holder};
+use crate::tasks::{self, Task};
+use crate::{run_log, ActivityMode, AppStateWithDir};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+// Run log lines shown as recent build, test and task results
+const RECENT_RESULTS: usize = 10;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+pub struct StatusReport {
+    /// planning, developing or error; None when read from disk without a server
+    pub mode: Option<String>,
+    pub needs_human_reason: Option<String>,
+    pub roles: BTreeMap<String, String>,
+    pub current_task: Option<Task>,
+    pub tasks: Vec<Task>,
+    pub recent: Vec<String>,
+    /// Who is working on the repository; only known to the server
+    #[serde(default)]
+    pub repo_lock: Vec<Holder>,
+}
+
+pub fn mode_name(mode: &ActivityMode) -> &'static str {
+    match mode {
+        ActivityMode::Planning => "planning",
+        ActivityMode::Developing => "developing",
+        ActivityMode::ErrorNeedsHuman => "error",
+    }
+}
+
+/// What can be known from the project directory alone: tasks and the run log.
+pub fn from_disk(project_dir: &str) -> StatusReport {
+    let tasks = tasks::read_tasks(project_dir);
+    StatusReport {
+        mode: None,
+        needs_human_reason: None,
+        roles: BTreeMap::new(),
+        current_task: tasks.iter().find(|t| !t.done).cloned(),
+        tasks,
+        recent: run_log::recent(Path::new(project_dir), RECENT_RESULTS),
+        repo_lock: Vec::new(),
+    }
+}
+
+pub async fn collect(state: &AppStateWithDir) -> StatusReport {
+    let mut report = from_disk(&state.project_dir);
+    report.mode = Some(mode_name(&*state.activity_mode.lock().await).to_string());
+    report.needs_human_reason = state.needs_human_reason.lock().await.clone();
+    report.roles = state.role_states.lock().await.clone();
+    report.repo_lock = state.repo_lock.holders();
+    report
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Asks the server, falling back to the project directory when it isn't running.
+async fn fetch(args: &ClientArgs) -> StatusReport {
+    let url = format!("{}/status", args.server.trim_end_matches('/'));
+    match client().get(&url).send().await {
+        Ok(response) if response.status().is_success() => match response.json().await {
+            Ok(report) => return report,
+            Err(e) => eprintln!("Unexpected response from {}: {}", url, e),
+        },
+        Ok(response) => eprintln!("{} answered {}", url, response.status()),
+        Err(_) => eprintln!("No server at {}, reading {}", args.server, args.project_dir),
+    }
+    from_disk(&args.project_dir)
+}
+
+fn print_tasks(tasks: &[Task]) {
+    if tasks.is_empty() {
+        println!("No tasks in TASKS.md yet");
+        return;
+    }
+    for task in tasks {
+        let mark = if task.done { "✓".bright_green() } else { " ".normal() };
+        let depends = if task.depends_on.is_empty() {
+            String::new()
+        } else {
+            let labels: Vec<String> = task
+                .depends_on
+                .iter()
+                .map(|n| {
+                    tasks
+                        .iter()
+                        .find(|t| t.number == *n)
+                        .map(|t| t.label.clone())
+                        .unwrap_or_else(|| n.to_string())
+                })
+                .collect();
+            format!(" (depends on {})", labels.join(", ")).dimmed().to_string()
+        };
+        println!("[{}] {}. {}{}", mark, task.label, task.title, depends);
+    }
+}
+
+pub async fn status(args: &ClientArgs) {
+    let report = fetch(args).await;
+    let mode = report.mode.as_deref().unwrap_or("unknown, server not running");
+    println!("{} {}", "Mode:".bright_green(), mode.yellow());
+    if let Some(reason) = &report.needs_human_reason {
+        println!("{} {}", "Needs a human:".bright_red(), reason);
+    }
+
+    if !report.roles.is_empty() {
+        println!("{}", "Roles:".bright_green());
+        for (role, state) in &report.roles {
+            println!("  {:<16} {}", role, state);
+        }
+    }
+
+    for holder in &report.repo_lock {
+        let access = match holder.access {
+            Access::Read => "reading",
+            Access::Write => "writing",
+        };
+        println!("{} {} {} since {}", "Repository:".bright_green(), holder.who, access, holder.since);
+    }
+
+    let done = report.tasks.iter().filter(|t| t.done).count();
+    println!("{} {}/{} done", "Tasks:".bright_green(), done, report.tasks.len());
+    if let Some(task) = &report.current_task {
+        println!("{} {}. {}", "Current task:".bright_green(), task.label, task.title);
+    }
+
+    if !report.recent.is_empty() {
+        println!("{}", "Recent results:".bright_green());
+        for line in &report.recent {
+            println!("  {}", line);
+        }
+    }
+}
+
+pub async fn tasks(args: &ClientArgs) {
+    print_tasks(&fetch(args).await.tasks);
+}
+
+/// Switches the running server's mode, returning an error when it can't be reached.
+pub async fn set_mode(args: &ModeArgs) -> Result<(), String> {
+    let url = format!("{}/toggle-mode", args.client.server.trim_end_matches('/'));
+    let response = client()
+        .post(&url)
+        .json(&serde_json::json!({ "mode": args.mode }))
+        .send()
+        .await
+        .map_err(|e| format!("Could not reach the server at {}: {}", args.client.server, e))?;
+    if !response.status().is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Failed to change mode: {}", body));
+    }
+    println!("Mode changed to {}", args.mode);
+    Ok(())
+}
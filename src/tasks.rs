@@ -0,0 +1,339 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Task {
+    /// Identifies the task; the label's parts in base 1000, so "3" is 3 and "1.2" is 1002
+    pub number: usize,
+    /// The number as TASKS.md writes it, e.g. "3" or "1.2" for a task under a phase
+    pub label: String,
+    pub title: String,
+    pub done: bool,
+    pub depends_on: Vec<usize>,
+    #[serde(skip)]
+    line: usize,
+}
+
+fn is_done_marker(line: &str) -> bool {
+    line.contains('✓') || line.contains('✔') || line.contains("[x]") || line.contains("[X]")
+}
+
+// Parses a task heading such as "1. Setup", "## Task 2: Models", "- [ ] 3) Routes" or
+// "### 1.2 Database" into the parts of its number and its title
+fn parse_task_heading(line: &str) -> Option<(Vec<usize>, String)> {
+    if line.starts_with("  ") || line.starts_with('\t') {
+        return None;
+    }
+    let mut rest = line.trim_start_matches(|c: char| c == '#' || c == '-' || c == '*' || c.is_whitespace());
+    for checkbox in ["[ ]", "[x]", "[X]"] {
+        rest = rest.strip_prefix(checkbox).unwrap_or(rest).trim_start();
+    }
+    for prefix in ["Task", "task", "TASK"] {
+        rest = rest.strip_prefix(prefix).unwrap_or(rest).trim_start();
+    }
+    rest = rest.trim_start_matches("**");
+
+    let mut path = Vec::new();
+    loop {
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if digits.is_empty() {
+            break;
+        }
+        path.push(digits.parse().ok()?);
+        rest = &rest[digits.len()..];
+        // "1.2" goes on, while the dot in "1. Setup" ends the number
+        match rest.strip_prefix('.') {
+            Some(after) if after.starts_with(|c: char| c.is_ascii_digit()) => rest = after,
+            _ => break,
+        }
+    }
+    if path.is_empty() {
+        return None;
+    }
+    let title = if rest.starts_with(['.', ')', ':']) {
+        &rest[1..]
+    } else if path.len() > 1 && rest.starts_with(char::is_whitespace) {
+        // "1.2 Database" needs no punctuation, a dotted number is never prose
+        rest
+    } else {
+        return None;
+    };
+    let title = title
+        .replace(['✓', '✔'], "")
+        .trim_matches(|c: char| c == '*' || c.is_whitespace())
+        .to_string();
+    Some((path, title))
+}
+
+// A bare "2. Add deps" line, which is only a task when nothing in the file is marked up
+// as one; under "## Task 1: Setup" it is a step of that task
+fn is_plain_heading(line: &str, path: &[usize]) -> bool {
+    path.len() == 1 && line.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn label(path: &[usize]) -> String {
+    path.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(".")
+}
+
+fn number(path: &[usize]) -> usize {
+    path.iter().fold(0, |number, part| number * 1000 + part)
+}
+
+// The task labels on a "Depends on: 1, 1.2" line, None when the text has no such line
+fn parse_dependencies(text: &str) -> Option<Vec<String>> {
+    let lower = text.to_lowercase();
+    let start = lower.find("depends on")? + "depends on".len();
+    let rest = &lower[start..];
+    let end = rest.find([')', ']', '\n']).unwrap_or(rest.len());
+    Some(
+        rest[..end]
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .map(|label| label.trim_matches('.'))
+            .filter(|label| !label.is_empty())
+            .map(|label| label.to_string())
+            .collect(),
+    )
+}
+
+pub fn parse_tasks(content: &str) -> Vec<Task> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut seen = HashSet::new();
+
+    let candidates: Vec<(usize, Vec<usize>, String)> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| parse_task_heading(line).map(|(p, t)| (i, p, t)))
+        .collect();
+    let marked_up = candidates
+        .iter()
+        .any(|(line, path, _)| !is_plain_heading(lines[*line], path));
+    let headings: Vec<(usize, Vec<usize>, String)> = candidates
+        .into_iter()
+        .filter(|(line, path, _)| !marked_up || !is_plain_heading(lines[*line], path))
+        .filter(|(_, path, _)| seen.insert(path.clone()))
+        .collect();
+
+    // A heading with numbered tasks under it, like "## 1. Foundation" above "1.1", is a phase
+    let is_phase = |path: &[usize]| {
+        headings
+            .iter()
+            .any(|(_, other, _)| other.len() > path.len() && other.starts_with(path))
+    };
+
+    for (index, (line, path, title)) in headings.iter().enumerate() {
+        if is_phase(path) {
+            continue;
+        }
+        let block_end = headings
+            .get(index + 1)
+            .map(|(next, _, _)| *next)
+            .unwrap_or(lines.len());
+        let block = lines[*line..block_end].join("\n");
+
+        // Without a "Depends on" line a task waits for the one listed before it, only
+        // "Depends on: none" makes it independent. Depending on a phase means depending on
+        // every task in it.
+        let depends_on = match parse_dependencies(&block) {
+            None => tasks.last().map(|t| vec![t.number]).unwrap_or_default(),
+            Some(dependencies) => dependencies
+                .iter()
+                .filter_map(|dependency| dependency.split('.').map(|p| p.parse().ok()).collect::<Option<Vec<usize>>>())
+                .flat_map(|dependency| {
+                    let mut numbers: Vec<usize> = headings
+                        .iter()
+                        .filter(|(_, other, _)| other.len() > dependency.len() && other.starts_with(&dependency))
+                        .filter(|(_, other, _)| !is_phase(other))
+                        .map(|(_, other, _)| number(other))
+                        .collect();
+                    if numbers.is_empty() {
+                        numbers.push(number(&dependency));
+                    }
+                    numbers
+                })
+                .collect(),
+        };
+
+        tasks.push(Task {
+            number: number(path),
+            label: label(path),
+            title: title.clone(),
+            done: is_done_marker(lines[*line]),
+            depends_on,
+            line: *line,
+        });
+    }
+
+    tasks
+}
+
+pub fn read_tasks(project_dir: &str) -> Vec<Task> {
+    let tasks_path = Path::new(project_dir).join("TASKS.md");
+    fs::read_to_string(tasks_path)
+        .map(|content| parse_tasks(&content))
+        .unwrap_or_default()
+}
+
+/// Uncompleted tasks whose dependencies are all done and that nobody has claimed yet.
+pub fn ready_tasks(tasks: &[Task], claimed: &HashSet<usize>) -> Vec<Task> {
+    let done: HashSet<usize> = tasks.iter().filter(|t| t.done).map(|t| t.number).collect();
+    let known: HashSet<usize> = tasks.iter().map(|t| t.number).collect();
+    tasks
+        .iter()
+        .filter(|t| !t.done && !claimed.contains(&t.number))
+        .filter(|t| {
+            t.depends_on
+                .iter()
+                .all(|d| done.contains(d) || !known.contains(d))
+        })
+        .cloned()
+        .collect()
+}
+
+pub fn mark_task_done(project_dir: &str, number: usize) -> Result<(), String> {
+    let tasks_path = Path::new(project_dir).join("TASKS.md");
+    let content = fs::read_to_string(&tasks_path)
+        .map_err(|e| format!("Failed to read TASKS.md: {}", e))?;
+
+    let task = parse_tasks(&content)
+        .into_iter()
+        .find(|t| t.number == number)
+        .ok_or_else(|| format!("Task {} not found in TASKS.md", number))?;
+    if task.done {
+        return Ok(());
+    }
+
+    let updated: Vec<String> = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == task.line {
+                format!("{} ✓", line.trim_end())
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    let mut updated = updated.join("\n");
+    if content.ends_with('\n') {
+        updated.push('\n');
+    }
+
+    fs::write(&tasks_path, updated).map_err(|e| format!("Failed to write TASKS.md: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|t| t.label.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_task_headings() {
+        assert_eq!(parse_task_heading("1. Setup"), Some((vec![1], "Setup".to_string())));
+        assert_eq!(parse_task_heading("## Task 2: Models"), Some((vec![2], "Models".to_string())));
+        assert_eq!(parse_task_heading("- [ ] 3) Routes"), Some((vec![3], "Routes".to_string())));
+        assert_eq!(parse_task_heading("- [x] **4. Deploy** ✓"), Some((vec![4], "Deploy".to_string())));
+        assert_eq!(parse_task_heading("### 1.2 Database"), Some((vec![1, 2], "Database".to_string())));
+        assert_eq!(parse_task_heading("- [ ] 2.1. Sign up"), Some((vec![2, 1], "Sign up".to_string())));
+    }
+
+    #[test]
+    fn ignores_lines_that_are_not_task_headings() {
+        assert_eq!(parse_task_heading("# Tasks"), None);
+        assert_eq!(parse_task_heading("## Phase 1: Foundation"), None);
+        assert_eq!(parse_task_heading("  1. An indented step"), None);
+        assert_eq!(parse_task_heading("2 hours of work"), None);
+        assert_eq!(parse_task_heading("Depends on: 1, 3"), None);
+    }
+
+    #[test]
+    fn parses_dependencies() {
+        assert_eq!(parse_dependencies("Depends on: 1, 3"), Some(vec!["1".to_string(), "3".to_string()]));
+        assert_eq!(parse_dependencies("Depends on: none"), Some(vec![]));
+        assert_eq!(parse_dependencies("- [ ] 4. Routes (depends on task 2.)"), Some(vec!["2".to_string()]));
+        assert_eq!(
+            parse_dependencies("Depends on: 1.1 and 1.2\nEstimate: 2 hours"),
+            Some(vec!["1.1".to_string(), "1.2".to_string()])
+        );
+        assert_eq!(parse_dependencies("Estimate: 2 hours"), None);
+    }
+
+    #[test]
+    fn tasks_without_dependencies_wait_for_the_one_before() {
+        let tasks = parse_tasks(
+            "Tasks for today\n\n1. Setup\n2. Models\nEstimate: 1 hour\n3. Docs\nDepends on: none\n4. Routes\nDepends on: 1\n",
+        );
+        assert_eq!(labels(&tasks), ["1", "2", "3", "4"]);
+        assert!(tasks[0].depends_on.is_empty());
+        assert_eq!(tasks[1].depends_on, [1]);
+        assert!(tasks[2].depends_on.is_empty());
+        assert_eq!(tasks[3].depends_on, [1]);
+
+        let ready = ready_tasks(&tasks, &HashSet::new());
+        assert_eq!(labels(&ready), ["1", "3"]);
+    }
+
+    #[test]
+    fn numbered_steps_under_task_headings_are_not_tasks() {
+        let tasks = parse_tasks(
+            "# Tasks
+
+## Task 1: Setup ✓
+1. Create project
+2. Add deps
+
+## Task 2: Models
+Depends on: 1
+1. Add the user model
+2. Add migrations
+3. Seed data
+",
+        );
+        assert_eq!(labels(&tasks), ["1", "2"]);
+        assert_eq!(tasks[0].title, "Setup");
+        assert!(tasks[0].done);
+        assert_eq!(tasks[1].title, "Models");
+        assert_eq!(tasks[1].depends_on, [1]);
+        assert_eq!(labels(&ready_tasks(&tasks, &HashSet::new())), ["2"]);
+    }
+
+    #[test]
+    fn phases_are_not_tasks() {
+        let tasks = parse_tasks(
+            "# Tasks
+
+## 1. Foundation
+### 1.1 Create the repository ✓
+Depends on: none
+### 1.2 Add the database
+Depends on: 1.1
+
+## 2. Features
+- [ ] 2.1. Sign up
+  Depends on: 1
+- [ ] 2.2. Log in
+  Depends on: 2.1
+",
+        );
+        assert_eq!(labels(&tasks), ["1.1", "1.2", "2.1", "2.2"]);
+        assert_eq!(tasks[1].title, "Add the database");
+        assert_eq!(tasks[1].depends_on, [1001]);
+        // Depending on a phase waits for every task in it
+        assert_eq!(tasks[2].depends_on, [1001, 1002]);
+
+        let ready = ready_tasks(&tasks, &HashSet::new());
+        assert_eq!(labels(&ready), ["1.2"]);
+    }
+
+    #[test]
+    fn claimed_tasks_are_not_ready() {
+        let tasks = parse_tasks("1. Setup\n2. Models\nDepends on: none\n");
+        let claimed = HashSet::from([1]);
+        assert_eq!(labels(&ready_tasks(&tasks, &claimed)), ["2"]);
+    }
+}
@@ -0,0 +1,244 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TEMPLATE_FILE: &str = "template.json";
+
+const BUNDLED: [&str; 5] = [
+    include_str!("templates/rust.json"),
+    include_str!("templates/python.json"),
+    include_str!("templates/javascript.json"),
+    include_str!("templates/typescript.json"),
+    include_str!("templates/go.json"),
+];
+
+// Planning documents every project gets, ahead of the template's own globs
+const CONTEXT_HEADER: &str = r#"/add TASKS.md
+/read-only ARCHITECTURE.md
+/read-only PROJECT.md
+/read-only TEST_STRATEGY.md
+/read-only Makefile"#;
+
+/// A project template: what aider works on, how to build and test, and what to start from.
+///
+/// User templates live in `~/.config/colossus/templates/<lang>/template.json`, with optional
+/// starter files under `files/`, and replace a bundled template of the same name.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Template {
+    pub name: String,
+    pub aliases: Vec<String>,
+    /// Manifest files that identify an existing project in this language
+    pub detect: Vec<String>,
+    /// Files aider should edit, written to CONTEXT.md as `/add <glob>`
+    pub context_globs: Vec<String>,
+    /// Commands for the Makefile's build and test targets
+    pub build: Vec<String>,
+    pub test: Vec<String>,
+    pub gitignore: Vec<String>,
+    /// Starter files by path relative to the project
+    pub files: BTreeMap<String, String>,
+    #[serde(skip)]
+    pub files_dir: Option<PathBuf>,
+}
+
+impl Template {
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    }
+
+    /// Loads a template directory. Without a `template.json` the whole directory is starter files.
+    pub fn from_dir(dir: &Path) -> Result<Template, String> {
+        let dir_name = dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let template_path = dir.join(TEMPLATE_FILE);
+        if !template_path.exists() {
+            return Ok(Template {
+                name: dir_name,
+                files_dir: Some(dir.to_path_buf()),
+                ..Default::default()
+            });
+        }
+
+        let content = fs::read_to_string(&template_path)
+            .map_err(|e| format!("Failed to read '{}': {}", template_path.display(), e))?;
+        let mut template: Template = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid template '{}': {}", template_path.display(), e))?;
+        if template.name.is_empty() {
+            template.name = dir_name;
+        }
+        let files_dir = dir.join("files");
+        template.files_dir = files_dir.is_dir().then_some(files_dir);
+        Ok(template)
+    }
+
+    /// Fits the build and test commands to an existing project. Recipes of the project's
+    /// own Makefile targets are kept, steps the project has no script for are dropped, e.g.
+    /// `npm run build` without a `build` script in package.json, and `pip install -r
+    /// requirements.txt` becomes `pip install -e .` when there is only a pyproject.toml.
+    /// Returns warnings about commands that are kept but may not work yet.
+    pub fn fit_to_project(&mut self, dir: &Path) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let makefile = fs::read_to_string(dir.join("Makefile")).unwrap_or_default();
+        let own_build = makefile_recipe(&makefile, "build");
+        let own_test = makefile_recipe(&makefile, "test");
+
+        if let Ok(package) = fs::read_to_string(dir.join("package.json")) {
+            let scripts = serde_json::from_str::<serde_json::Value>(&package)
+                .map(|p| p["scripts"].clone())
+                .unwrap_or_default();
+            if scripts["build"].is_null() {
+                self.build.retain(|c| c != "npm run build");
+            }
+            // npm init's placeholder test script fails on purpose
+            let test = scripts["test"].as_str().unwrap_or_default();
+            if own_test.is_none()
+                && self.test.iter().any(|c| c == "npm test")
+                && (test.is_empty() || test.contains("no test specified"))
+            {
+                warnings.push("package.json has no test script, `make test` fails until one is added".to_string());
+            }
+        }
+        if !dir.join("requirements.txt").exists() && dir.join("pyproject.toml").exists() {
+            for command in self.build.iter_mut() {
+                if command.contains("-r requirements.txt") {
+                    *command = "python -m pip install -e .".to_string();
+                }
+            }
+        }
+
+        if let Some(recipe) = own_build {
+            self.build = recipe;
+        }
+        if let Some(recipe) = own_test {
+            self.test = recipe;
+        }
+        warnings
+    }
+
+    /// CONTEXT.md contents, or None when the template leaves it to its starter files.
+    pub fn context(&self) -> Option<String> {
+        if self.context_globs.is_empty() {
+            return None;
+        }
+        let adds: Vec<String> = self.context_globs.iter().map(|g| format!("/add {}", g)).collect();
+        Some(format!("{}\n{}", CONTEXT_HEADER, adds.join("\n")))
+    }
+
+    /// Makefile contents, or None when the template defines no build or test commands.
+    pub fn makefile(&self) -> Option<String> {
+        if self.build.is_empty() && self.test.is_empty() {
+            return None;
+        }
+        let recipe = |commands: &[String]| {
+            commands
+                .iter()
+                .map(|c| format!("\t{}", c))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        Some(format!(
+            ".PHONY: build test\n\nbuild:\n{}\n\ntest:\n{}",
+            recipe(&self.build),
+            recipe(&self.test)
+        ))
+    }
+}
+
+// The commands of `target` in a Makefile, None when it has no such target or it is empty
+fn makefile_recipe(makefile: &str, target: &str) -> Option<Vec<String>> {
+    let mut lines = makefile.lines().skip_while(|line| {
+        line.starts_with('\t') || line.split_once(':').map(|(t, _)| t.trim()) != Some(target)
+    });
+    lines.next()?;
+    let recipe: Vec<String> = lines
+        .take_while(|line| line.starts_with('\t') || line.trim().is_empty())
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    (!recipe.is_empty()).then_some(recipe)
+}
+
+/// `$XDG_CONFIG_HOME/colossus/templates`, falling back to `~/.config/colossus/templates`.
+pub fn user_template_dir() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("colossus").join("templates"))
+}
+
+fn user_templates() -> Vec<Template> {
+    let Some(dir) = user_template_dir() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let mut templates = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.join(TEMPLATE_FILE).exists() {
+            continue;
+        }
+        match Template::from_dir(&path) {
+            Ok(template) => templates.push(template),
+            Err(e) => eprintln!("Skipping template: {}", e),
+        }
+    }
+    templates
+}
+
+/// Bundled templates, overridden or extended by the user's own.
+pub fn available() -> Vec<Template> {
+    let mut templates: Vec<Template> = BUNDLED
+        .iter()
+        .map(|json| serde_json::from_str(json).expect("bundled template is valid"))
+        .collect();
+
+    let mut user = user_templates();
+    user.sort_by(|a, b| a.name.cmp(&b.name));
+    for template in user {
+        match templates.iter_mut().find(|t| t.matches(&template.name)) {
+            Some(existing) => *existing = template,
+            None => templates.push(template),
+        }
+    }
+    templates
+}
+
+pub fn names(templates: &[Template]) -> String {
+    templates.iter().map(|t| t.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Picks the template whose manifest files best match an existing project, along with
+/// the manifests that matched.
+pub fn detect(dir: &Path) -> Option<(Template, Vec<String>)> {
+    available()
+        .into_iter()
+        .filter_map(|template| {
+            let found: Vec<String> = template
+                .detect
+                .iter()
+                .filter(|manifest| dir.join(manifest).exists())
+                .cloned()
+                .collect();
+            (!found.is_empty()).then_some((template, found))
+        })
+        // tsconfig.json and package.json is TypeScript, package.json alone is JavaScript
+        .max_by_key(|(template, found)| (found.len(), found.len() == template.detect.len()))
+}
+
+/// Finds a template by name or alias, case-insensitively.
+pub fn find(name: &str) -> Result<Template, String> {
+    let templates = available();
+    templates
+        .iter()
+        .find(|t| t.matches(name))
+        .cloned()
+        .ok_or_else(|| format!("Unsupported language '{}'. Choose one of: {}", name, names(&templates)))
+}
@@ -1,4 +1,5 @@
-use crate::{ActivityMode, AppStateWithDir};
+use crate::events::Verbosity;
+use crate::{code_model_name, ActivityMode, AppStateWithDir};
 use std::fs;
 use std::process::Command;
 use std::sync::Arc;
@@ -30,6 +31,11 @@ pub async fn tester_loop(
         };
 
         if !should_continue {
+            state_with_dir.set_role_state("tester", "idle").await;
+            continue;
+        }
+        if state_with_dir.usage.is_paused("tester").await {
+            state_with_dir.set_role_state("tester", "paused, budget exceeded").await;
             continue;
         }
 
@@ -64,6 +70,8 @@ pub async fn tester_loop(
         };
 
         if should_run_aider {
+            let _repo = state_with_dir.repo_lock.write("tester").await;
+            state_with_dir.set_role_state("tester", "updating TEST_STRATEGY.md").await;
             println!("🧪 Updating TEST_STRATEGY.md from architecture...");
             let mut cmd = Command::new("aider");
             cmd.current_dir(&project_dir)
@@ -95,10 +103,21 @@ Note: Do not include any CI/CD setup or pipeline configuration - this is strictl
                         String::from_utf8_lossy(&output.stderr)
                     );
                 } else {
+                    state_with_dir
+                        .usage
+                        .record_aider(
+                            "tester",
+                            None,
+                            code_model_name(&state_with_dir),
+                            &String::from_utf8_lossy(&output.stdout),
+                        )
+                        .await;
                     println!("✨ Aider finished updating TEST_STRATEGY.md");
+                    state_with_dir.comment(Verbosity::Verbose, "TEST_STRATEGY.md was updated from the architecture");
                 }
             }
         }
+        state_with_dir.set_role_state("tester", "up to date").await;
     }
     println!("Tester thread shutting down cleanly");
 }
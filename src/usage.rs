@@ -0,0 +1,355 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+#[derive(Serialize, Clone, Default)]
+pub struct UsageEntry {
+    pub provider: String,
+    pub model: String,
+    pub role: String,
+    pub task: Option<String>,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+#[derive(Serialize)]
+pub struct UsageReport {
+    pub total_cost_usd: f64,
+    pub total_input_tokens: u64,
+    pub total_output_tokens: u64,
+    pub budget_usd: Option<f64>,
+    pub role_budgets_usd: HashMap<String, f64>,
+    pub paused_roles: Vec<String>,
+    pub entries: Vec<UsageEntry>,
+}
+
+/// Who a model call is billed to.
+pub struct UsageContext<'a> {
+    pub tracker: &'a UsageTracker,
+    pub role: &'a str,
+    pub task: Option<&'a str>,
+}
+
+/// Accumulates tokens and cost per provider, model, role and task, and pauses roles
+/// whose spending goes over their budget.
+pub struct UsageTracker {
+    entries: Mutex<Vec<UsageEntry>>,
+    budget: Option<f64>,
+    role_budgets: HashMap<String, f64>,
+}
+
+// "2.3k" -> 2300, "1.1M" -> 1100000
+fn parse_count(text: &str) -> u64 {
+    let text = text.trim();
+    let (number, multiplier) = match text.chars().last() {
+        Some('k') | Some('K') => (&text[..text.len() - 1], 1_000.0),
+        Some('m') | Some('M') => (&text[..text.len() - 1], 1_000_000.0),
+        _ => (text, 1.0),
+    };
+    number
+        .replace(',', "")
+        .parse::<f64>()
+        .map(|n| (n * multiplier) as u64)
+        .unwrap_or(0)
+}
+
+/// Sums aider's report lines, e.g.
+/// `Tokens: 2.3k sent, 145 received. Cost: $0.0071 message, $0.02 session.`
+pub fn parse_aider_usage(output: &str) -> (u64, u64, f64) {
+    let mut totals = (0, 0, 0.0);
+    for line in output.lines() {
+        let Some(start) = line.find("Tokens: ") else {
+            continue;
+        };
+        let report = &line[start + "Tokens: ".len()..];
+        let (tokens, cost) = report.split_once(". Cost: ").unwrap_or((report, ""));
+
+        // Without a cost the line ends right after "received."
+        for part in tokens.trim_end().trim_end_matches('.').split(", ") {
+            if let Some(sent) = part.strip_suffix(" sent") {
+                totals.0 += parse_count(sent);
+            } else if let Some(received) = part.strip_suffix(" received") {
+                totals.1 += parse_count(received);
+            }
+        }
+        if let Some(message_cost) = cost
+            .split(" message")
+            .next()
+            .and_then(|c| c.trim().trim_start_matches('$').parse::<f64>().ok())
+        {
+            totals.2 += message_cost;
+        }
+    }
+    totals
+}
+
+// USD per million tokens: (text input, text output, audio input, audio output)
+fn realtime_prices(model: &str) -> (f64, f64, f64, f64) {
+    if model.contains("mini") {
+        (0.6, 2.4, 10.0, 20.0)
+    } else if model.contains("realtime") {
+        (5.0, 20.0, 40.0, 80.0)
+    } else {
+        (0.0, 0.0, 0.0, 0.0)
+    }
+}
+
+// USD per million tokens: (input, output), unknown and local models count as free
+fn chat_prices(model: &str) -> (f64, f64) {
+    if model.starts_with("gpt-4o-mini") {
+        (0.15, 0.6)
+    } else if model.starts_with("gpt-4o") {
+        (2.5, 10.0)
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+// USD per million input tokens, per million output tokens, and per thousand requests
+fn perplexity_prices(model: &str) -> (f64, f64, f64) {
+    if model.contains("huge") {
+        (5.0, 5.0, 5.0)
+    } else if model.contains("large") {
+        (1.0, 1.0, 5.0)
+    } else {
+        (0.2, 0.2, 5.0)
+    }
+}
+
+impl UsageTracker {
+    pub fn new(budget: Option<f64>, role_budgets: HashMap<String, f64>) -> Self {
+        UsageTracker {
+            entries: Mutex::new(Vec::new()),
+            budget,
+            role_budgets,
+        }
+    }
+
+    pub fn context<'a>(&'a self, role: &'a str, task: Option<&'a str>) -> UsageContext<'a> {
+        UsageContext {
+            tracker: self,
+            role,
+            task,
+        }
+    }
+
+    pub async fn record(&self, usage: UsageEntry) {
+        let mut entries = self.entries.lock().await;
+        let was_paused = self.paused(&entries, &usage.role);
+
+        match entries.iter_mut().find(|e| {
+            e.provider == usage.provider && e.model == usage.model && e.role == usage.role && e.task == usage.task
+        }) {
+            Some(entry) => {
+                entry.requests += usage.requests;
+                entry.input_tokens += usage.input_tokens;
+                entry.output_tokens += usage.output_tokens;
+                entry.cost_usd += usage.cost_usd;
+            }
+            None => entries.push(usage.clone()),
+        }
+
+        if !was_paused && self.paused(&entries, &usage.role) {
+            println!("💸 Budget exceeded, pausing {}", usage.role);
+        }
+    }
+
+    pub async fn record_aider(&self, role: &str, task: Option<&str>, model: &str, output: &str) {
+        let (input_tokens, output_tokens, cost_usd) = parse_aider_usage(output);
+        self.record(UsageEntry {
+            provider: "aider".to_string(),
+            model: model.to_string(),
+            role: role.to_string(),
+            task: task.map(|t| t.to_string()),
+            requests: 1,
+            input_tokens,
+            output_tokens,
+            cost_usd,
+        })
+        .await;
+    }
+
+    /// Records the `usage` object of a Realtime `response.done` event.
+    pub async fn record_realtime(&self, model: &str, usage: &serde_json::Value) {
+        let count = |value: &serde_json::Value| value.as_u64().unwrap_or(0);
+        let input = &usage["input_token_details"];
+        let output = &usage["output_token_details"];
+        let (text_in, text_out, audio_in, audio_out) = realtime_prices(model);
+        let cost_usd = (count(&input["text_tokens"]) as f64 * text_in
+            + count(&output["text_tokens"]) as f64 * text_out
+            + count(&input["audio_tokens"]) as f64 * audio_in
+            + count(&output["audio_tokens"]) as f64 * audio_out)
+            / 1_000_000.0;
+
+        self.record(UsageEntry {
+            provider: "openai-realtime".to_string(),
+            model: model.to_string(),
+            role: "interviewer".to_string(),
+            task: None,
+            requests: 1,
+            input_tokens: count(&usage["input_tokens"]),
+            output_tokens: count(&usage["output_tokens"]),
+            cost_usd,
+        })
+        .await;
+    }
+
+    /// Records the `usage` object of a text chat completion.
+    pub async fn record_chat(&self, model: &str, usage: &serde_json::Value) {
+        let input_tokens = usage["prompt_tokens"].as_u64().unwrap_or(0);
+        let output_tokens = usage["completion_tokens"].as_u64().unwrap_or(0);
+        let (input_price, output_price) = chat_prices(model);
+
+        self.record(UsageEntry {
+            provider: "chat".to_string(),
+            model: model.to_string(),
+            role: "interviewer".to_string(),
+            task: None,
+            requests: 1,
+            input_tokens,
+            output_tokens,
+            cost_usd: (input_tokens as f64 * input_price + output_tokens as f64 * output_price) / 1_000_000.0,
+        })
+        .await;
+    }
+
+    /// Records the `usage` object of a Perplexity chat completion.
+    pub async fn record_perplexity(&self, model: &str, usage: &serde_json::Value) {
+        let input_tokens = usage["prompt_tokens"].as_u64().unwrap_or(0);
+        let output_tokens = usage["completion_tokens"].as_u64().unwrap_or(0);
+        let (input_price, output_price, request_price) = perplexity_prices(model);
+
+        self.record(UsageEntry {
+            provider: "perplexity".to_string(),
+            model: model.to_string(),
+            role: "web_search".to_string(),
+            task: None,
+            requests: 1,
+            input_tokens,
+            output_tokens,
+            cost_usd: (input_tokens as f64 * input_price + output_tokens as f64 * output_price) / 1_000_000.0
+                + request_price / 1000.0,
+        })
+        .await;
+    }
+
+    fn paused(&self, entries: &[UsageEntry], role: &str) -> bool {
+        let total: f64 = entries.iter().map(|e| e.cost_usd).sum();
+        let role_total: f64 = entries.iter().filter(|e| e.role == role).map(|e| e.cost_usd).sum();
+        self.budget.is_some_and(|budget| total >= budget)
+            || self.role_budgets.get(role).is_some_and(|budget| role_total >= *budget)
+    }
+
+    /// Whether `role` has used up its own budget or the session budget.
+    pub async fn is_paused(&self, role: &str) -> bool {
+        let entries = self.entries.lock().await;
+        self.paused(&entries, role)
+    }
+
+    pub async fn report(&self) -> UsageReport {
+        let entries = self.entries.lock().await;
+        let mut roles: Vec<&str> = self.role_budgets.keys().map(|r| r.as_str()).collect();
+        roles.extend(entries.iter().map(|e| e.role.as_str()));
+        roles.sort();
+        roles.dedup();
+
+        UsageReport {
+            total_cost_usd: entries.iter().map(|e| e.cost_usd).sum(),
+            total_input_tokens: entries.iter().map(|e| e.input_tokens).sum(),
+            total_output_tokens: entries.iter().map(|e| e.output_tokens).sum(),
+            budget_usd: self.budget,
+            role_budgets_usd: self.role_budgets.clone(),
+            paused_roles: roles
+                .into_iter()
+                .filter(|role| self.paused(&entries, role))
+                .map(|role| role.to_string())
+                .collect(),
+            entries: entries.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn parses_token_counts() {
+        assert_eq!(parse_count("145"), 145);
+        assert_eq!(parse_count("2.3k"), 2300);
+        assert_eq!(parse_count("1.1M"), 1_100_000);
+        assert_eq!(parse_count("12,345"), 12_345);
+        assert_eq!(parse_count("many"), 0);
+    }
+
+    #[test]
+    fn sums_aider_reports() {
+        let output = "Applied edit to src/main.rs
+Tokens: 2.3k sent, 145 received. Cost: $0.0071 message, $0.02 session.
+Commit 1a2b3c4 Add routes
+Tokens: 12k sent, 1.2k cache write, 3.4k cache hit, 1.1k received. Cost: $0.05 message, $0.07 session.
+";
+        let (sent, received, cost) = parse_aider_usage(output);
+        assert_eq!(sent, 14_300);
+        assert_eq!(received, 1_245);
+        // Only the per-message cost counts, the session total would count twice
+        assert!(close(cost, 0.0571));
+    }
+
+    #[test]
+    fn aider_reports_without_cost_count_tokens_only() {
+        assert_eq!(parse_aider_usage("Tokens: 900 sent, 80 received."), (900, 80, 0.0));
+        assert_eq!(parse_aider_usage("No changes made."), (0, 0, 0.0));
+    }
+
+    #[tokio::test]
+    async fn prices_realtime_and_chat_usage() {
+        let tracker = UsageTracker::new(None, HashMap::new());
+        tracker
+            .record_realtime(
+                "gpt-4o-realtime-preview",
+                &json!({
+                    "input_tokens": 1_100_000,
+                    "output_tokens": 1_500_000,
+                    "input_token_details": {"text_tokens": 100_000, "audio_tokens": 1_000_000},
+                    "output_token_details": {"text_tokens": 500_000, "audio_tokens": 1_000_000}
+                }),
+            )
+            .await;
+        tracker
+            .record_chat("gpt-4o-mini", &json!({"prompt_tokens": 1_000_000, "completion_tokens": 1_000_000}))
+            .await;
+        tracker
+            .record_chat("llama3", &json!({"prompt_tokens": 1_000_000, "completion_tokens": 1_000_000}))
+            .await;
+
+        let report = tracker.report().await;
+        assert_eq!(report.entries.len(), 3);
+        // 0.1M text in at $5, 0.5M text out at $20, 1M audio in at $40, 1M audio out at $80
+        assert!(close(report.entries[0].cost_usd, 0.5 + 10.0 + 40.0 + 80.0));
+        assert!(close(report.entries[1].cost_usd, 0.75));
+        assert!(close(report.entries[2].cost_usd, 0.0));
+        assert_eq!(report.total_input_tokens, 3_100_000);
+    }
+
+    #[tokio::test]
+    async fn pauses_roles_over_budget() {
+        let tracker = UsageTracker::new(Some(1.0), HashMap::from([("architect".to_string(), 0.1)]));
+        let output = "Tokens: 1k sent, 1k received. Cost: $0.20 message, $0.20 session.";
+        tracker.record_aider("architect", None, "gpt-4o", output).await;
+        assert!(tracker.is_paused("architect").await);
+        assert!(!tracker.is_paused("developer").await);
+
+        let output = "Tokens: 9k sent, 9k received. Cost: $0.90 message, $1.10 session.";
+        tracker.record_aider("developer", Some("task 1: Setup"), "gpt-4o", output).await;
+        assert!(tracker.is_paused("developer").await);
+        assert_eq!(tracker.report().await.paused_roles, ["architect", "developer"]);
+    }
+}
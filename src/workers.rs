@@ -0,0 +1,314 @@
+use crate::{aider, run_log};
+use crate::developer::{accept_unchanged_task, build_and_test, ensure_progress, write_failing_tests};
+use crate::events::Verbosity;
+use crate::git::{self, MergeOutcome};
+use crate::reviewer::review_until_approved;
+use crate::tasks::{self, Task};
+use crate::{ActivityMode, AppStateWithDir};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+
+const WORKTREE_DIR: &str = ".colossus/worktrees";
+
+// Planning documents the workers need to see in their worktrees
+const PLANNING_FILES: [&str; 6] = [
+    "CONTEXT.md",
+    "Makefile",
+    "PROJECT.md",
+    "ARCHITECTURE.md",
+    "TASKS.md",
+    "TEST_STRATEGY.md",
+];
+
+/// Shared between developer workers so each task is claimed once. Changes to the main
+/// working tree, such as merges, hold the repository lock so they happen one at a time.
+pub struct WorkerPool {
+    claimed: Mutex<HashSet<usize>>,
+}
+
+impl WorkerPool {
+    pub fn new() -> Self {
+        WorkerPool {
+            claimed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    async fn claim_next(&self, project_dir: &str) -> Option<Task> {
+        let mut claimed = self.claimed.lock().await;
+        let all_tasks = tasks::read_tasks(project_dir);
+        let task = tasks::ready_tasks(&all_tasks, &claimed).into_iter().next()?;
+        claimed.insert(task.number);
+        Some(task)
+    }
+
+    async fn release(&self, number: usize) {
+        self.claimed.lock().await.remove(&number);
+    }
+}
+
+fn task_branch(task: &Task) -> String {
+    format!("colossus/task-{}", task.label)
+}
+
+fn task_worktree(project_dir: &Path, task: &Task) -> PathBuf {
+    project_dir.join(WORKTREE_DIR).join(format!("task-{}", task.label))
+}
+
+fn task_label(task: &Task) -> String {
+    format!("task {}: {}", task.label, task.title)
+}
+
+/// Merges the main branch into the task branch and asks aider to resolve the conflicts
+/// there, so the retried merge into main is a fast, clean one.
+async fn repair_conflicts(
+    worktree: &Path,
+    main_branch: &str,
+    label: &str,
+    model: &str,
+    state_with_dir: &AppStateWithDir,
+) -> Result<(), String> {
+    if let MergeOutcome::Conflict(files) = git::merge_branch(worktree, main_branch).await? {
+        println!("🔀 Asking aider to resolve conflicts in {}", files.join(", "));
+        aider::run(
+            worktree,
+            model,
+            "Resolve the git merge conflicts in these files. Remove every conflict marker and keep the intent of both sides.",
+            &files,
+            &state_with_dir.usage.context("developer", Some(label)),
+        )
+        .await;
+
+        if let Err(e) = git::commit_all(worktree, &format!("Merge {} into task branch", main_branch)).await {
+            git::abort_merge(worktree).await;
+            return Err(e);
+        }
+    }
+
+    let worktree_dir = worktree.to_string_lossy();
+    build_and_test(&worktree_dir, label, model, state_with_dir).await
+}
+
+/// Merges the task branch into main and builds and tests main before letting go of the
+/// repository, since two branches that pass on their own can still break main together.
+/// Returns the conflicting files when the merge didn't go through.
+async fn merge_and_verify(
+    project_dir: &Path,
+    task: &Task,
+    model: &str,
+    state_with_dir: &AppStateWithDir,
+) -> Result<Option<Vec<String>>, String> {
+    let branch = task_branch(task);
+    let label = task_label(task);
+    let _merging = state_with_dir.repo_lock.write(&format!("merging task {}", task.label)).await;
+    match git::merge_branch(project_dir, &branch).await? {
+        MergeOutcome::Merged => {
+            build_and_test(&project_dir.to_string_lossy(), &label, model, state_with_dir)
+                .await
+                .map_err(|e| format!("Main fails after merging {}: {}", label, e))?;
+            Ok(None)
+        }
+        MergeOutcome::Conflict(files) => {
+            git::abort_merge(project_dir).await;
+            Ok(Some(files))
+        }
+    }
+}
+
+async fn merge_into_main(
+    project_dir: &Path,
+    task: &Task,
+    main_branch: &str,
+    model: &str,
+    state_with_dir: &AppStateWithDir,
+) -> Result<(), String> {
+    let branch = task_branch(task);
+    let worktree = task_worktree(project_dir, task);
+
+    let Some(files) = merge_and_verify(project_dir, task, model, state_with_dir).await? else {
+        return Ok(());
+    };
+    println!("⚠️  Merging {} conflicts in {}, repairing...", branch, files.join(", "));
+    repair_conflicts(&worktree, main_branch, &task_label(task), model, state_with_dir)
+        .await
+        .map_err(|e| format!("Could not repair merge conflicts on {}: {}", branch, e))?;
+
+    // One retry after the repair, the task goes to a human if main moved on again
+    match merge_and_verify(project_dir, task, model, state_with_dir).await? {
+        None => Ok(()),
+        Some(files) => Err(format!(
+            "Merging {} still conflicts in {} after repair",
+            branch,
+            files.join(", ")
+        )),
+    }
+}
+
+async fn work_on_task(
+    worker_id: usize,
+    task: &Task,
+    project_dir: &str,
+    main_branch: &str,
+    model: &str,
+    state_with_dir: &AppStateWithDir,
+) -> Result<(), String> {
+    let project_path = Path::new(project_dir);
+    let branch = task_branch(task);
+    let worktree = task_worktree(project_path, task);
+    let label = task_label(task);
+    let usage = state_with_dir.usage.context("developer", Some(&label));
+
+    // Make sure the worktree starts from the latest plan
+    {
+        let _merging = state_with_dir.repo_lock.write(&format!("worker {}", worker_id)).await;
+        if git::commit_paths(project_path, &PLANNING_FILES, "Update planning documents").await? {
+            println!("📝 Committed the changed planning documents so {} starts from them", label);
+            run_log::record(
+                project_path,
+                &format!("Committed changed planning documents before starting {}", label),
+            );
+        }
+        git::add_worktree(project_path, &worktree, &branch, main_branch).await?;
+    }
+
+    println!(
+        "👷 Worker {} implementing task {}: {} in {}",
+        worker_id,
+        task.label,
+        task.title,
+        worktree.display()
+    );
+    let worktree_dir = worktree.to_string_lossy().to_string();
+    let base = git::merge_base(&worktree, "HEAD", main_branch).await?;
+    let mut message = format!(
+        "Implement task {} from TASKS.md: \"{}\". Only work on this task, create some way to test it, and do not edit TASKS.md.",
+        task.label, task.title
+    );
+    // Progress is measured after the tests, which alone don't implement anything
+    let mut progress_base = base.clone();
+    if state_with_dir.tdd {
+        progress_base = write_failing_tests(&worktree_dir, &label, model, &usage).await?;
+        message = format!(
+            "Now implement {} so the tests you just wrote pass. Do not weaken or delete those tests, and do not edit TASKS.md.",
+            label
+        );
+    }
+    aider::run(&worktree, model, &message, &[], &usage).await;
+
+    if !ensure_progress(&worktree_dir, &progress_base, &label, model, &usage).await {
+        let writer = format!("worker {}", worker_id);
+        accept_unchanged_task(&worktree_dir, task, &label, model, state_with_dir, Some(&writer)).await?;
+        git::remove_worktree(project_path, &worktree, &branch).await?;
+        return Ok(());
+    }
+
+    build_and_test(&worktree_dir, &label, model, state_with_dir)
+        .await
+        .map_err(|e| format!("Task {} failed in {}: {}", task.label, worktree.display(), e))?;
+    if state_with_dir.tdd {
+        run_log::record(project_path, &format!("TDD green: tests for {} pass after implementation", label));
+    }
+
+    if state_with_dir.review {
+        review_until_approved(&worktree, &base, &label, model, state_with_dir).await?;
+    }
+
+    git::commit_all(&worktree, &format!("Implement task {}: {}", task.label, task.title)).await?;
+    merge_into_main(project_path, task, main_branch, model, state_with_dir).await?;
+
+    {
+        let _merging = state_with_dir.repo_lock.write(&format!("worker {}", worker_id)).await;
+        tasks::mark_task_done(project_dir, task.number)?;
+        git::commit_paths(project_path, &["TASKS.md"], &format!("Mark task {} done", task.label)).await?;
+    }
+    git::remove_worktree(project_path, &worktree, &branch).await?;
+
+    println!("✅ Worker {} merged task {} into {}", worker_id, task.label, main_branch);
+    state_with_dir.comment(
+        Verbosity::Important,
+        &format!("Task {}, {}, is done and build and tests pass", task.label, task.title),
+    );
+    Ok(())
+}
+
+pub async fn developer_worker_loop(
+    worker_id: usize,
+    project_dir: String,
+    shutdown_signal: Arc<Mutex<bool>>,
+    state_with_dir: Arc<AppStateWithDir>,
+    pool: Arc<WorkerPool>,
+) {
+    let mut interval = time::interval(Duration::from_secs(30));
+
+    loop {
+        interval.tick().await;
+
+        // Check if we should shutdown
+        {
+            let shutdown = shutdown_signal.lock().await;
+            if *shutdown {
+                break;
+            }
+        }
+
+        // Check activity mode quickly
+        let should_continue = {
+            let mode = state_with_dir.activity_mode.lock().await;
+            matches!(*mode, ActivityMode::Developing)
+        };
+
+        let role = format!("worker {}", worker_id);
+        if !should_continue {
+            state_with_dir.set_role_state(&role, "idle").await;
+            continue;
+        }
+        if state_with_dir.usage.is_paused("developer").await {
+            state_with_dir.set_role_state(&role, "paused, budget exceeded").await;
+            continue;
+        }
+
+        let Some(task) = pool.claim_next(&project_dir).await else {
+            state_with_dir.set_role_state(&role, "waiting for a ready task").await;
+            continue;
+        };
+        state_with_dir
+            .set_role_state(&role, &format!("working on {}", task_label(&task)))
+            .await;
+        state_with_dir.comment(
+            Verbosity::Normal,
+            &format!("Worker {} is starting task {}, {}", worker_id, task.label, task.title),
+        );
+
+        let model = state_with_dir
+            .code_model
+            .as_ref()
+            .expect("Code model should be set from CLI params");
+
+        let main_branch = match git::current_branch(Path::new(&project_dir)).await {
+            Ok(branch) => branch,
+            Err(e) => {
+                state_with_dir.needs_human(&e).await;
+                pool.release(task.number).await;
+                continue;
+            }
+        };
+
+        if let Err(e) = work_on_task(
+            worker_id,
+            &task,
+            &project_dir,
+            &main_branch,
+            model,
+            &state_with_dir,
+        )
+        .await
+        {
+            state_with_dir.needs_human(&e).await;
+        }
+        pool.release(task.number).await;
+        state_with_dir.set_role_state(&role, "idle").await;
+    }
+    println!("Developer worker {} shutting down cleanly", worker_id);
+}